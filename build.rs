@@ -1,7 +1,41 @@
 use std::{env, fs};
 
+#[path = "src/nnue/compress.rs"]
+mod compress;
+
+// These have to match `network.rs`.
+const INPUT: usize = 704;
+/// The SIMD inference code processes the hidden layer in chunks of this many neurons.
+const HL_ALIGN: usize = 128;
+const HEADER_SIZE: usize = 128;
+const NET_MAGIC: [u8; 4] = *b"ICNN";
+const NET_VERSION: u8 = 1;
+#[rustfmt::skip]
+const DEFAULT_KING_BUCKET_LAYOUT: [u8; 64] = [
+     0,  1,  2,  3,  3,  2,  1,  0,
+     4,  5,  6,  7,  7,  6,  5,  4,
+     8,  8,  9,  9,  9,  9,  8,  8,
+     8,  8,  9,  9,  9,  9,  8,  8,
+    10, 10, 11, 11, 11, 11, 10, 10,
+    10, 10, 11, 11, 11, 11, 10, 10,
+    12, 12, 13, 13, 13, 13, 12, 12,
+    12, 12, 13, 13, 13, 13, 12, 12,
+];
+
+/// The header of nets from before the header existed, see `NetHeader::DEFAULT`.
+fn default_header() -> Vec<u8> {
+    let mut header = vec![0; HEADER_SIZE];
+    header[..4].copy_from_slice(&NET_MAGIC);
+    header[4] = NET_VERSION;
+    header[5] = 14;
+    header[6] = 1;
+    header[64..].copy_from_slice(&DEFAULT_KING_BUCKET_LAYOUT);
+    header
+}
+
 fn main() {
     println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/nnue/compress.rs");
     println!("cargo:rerun-if-changed=nets/icarus.nnue");
     println!("cargo:rerun-if-env-changed=EVALFILE");
 
@@ -13,6 +47,55 @@ fn main() {
             "No net found! Use the Makefile, `download-net.py`, or specify a net path through the `EVALFILE` env var!"
         );
     }
+    println!("cargo:rerun-if-changed={in_path}");
+
+    let mut bytes = fs::read(&in_path).unwrap();
+    if compress::is_compressed(&bytes) {
+        bytes = compress::decompress(&bytes).unwrap_or_else(|e| panic!("`{in_path}`: {e}"));
+    }
+    if !bytes.starts_with(&NET_MAGIC) {
+        bytes.splice(0..0, default_header());
+    }
+
+    let (version, buckets, mirror) = (bytes[4], bytes[5] as usize, bytes[6]);
+    let (wdl_head, threats) = (bytes[7], bytes[8]);
+    let layout = &bytes[64..HEADER_SIZE];
+    if version != NET_VERSION {
+        panic!("`{in_path}` has unsupported net version {version}");
+    }
+    if buckets == 0 || mirror > 1 || layout.iter().any(|&b| b as usize >= buckets) {
+        panic!("`{in_path}` has an invalid king bucket layout");
+    }
+    if wdl_head > 1 {
+        panic!("`{in_path}` has an invalid WDL head flag {wdl_head}");
+    }
+    if threats > 1 {
+        panic!("`{in_path}` has an invalid threat inputs flag {threats}");
+    }
+
+    // The hidden layer size follows from the file size, which is checked here so that a broken
+    // net fails the build instead of the engine at startup. Every neuron has a weight per input
+    // and king bucket, a weight per threat input if it has them, a bias and two output weights, and
+    // the file is padded to a multiple of 64 bytes.
+    let size = bytes.len();
+    let threat_inputs = if threats == 1 { INPUT } else { 0 };
+    let (wdl_weights, biases) = if wdl_head == 1 { (6, 4) } else { (0, 1) };
+    let per_neuron = 2 * (buckets * INPUT + threat_inputs + 1 + 2 + wdl_weights);
+    let hl = size.saturating_sub(HEADER_SIZE + 2 * biases) / per_neuron;
+    if hl == 0
+        || !hl.is_multiple_of(HL_ALIGN)
+        || (HEADER_SIZE + hl * per_neuron + 2 * biases).next_multiple_of(64) != size
+    {
+        panic!(
+            "`{in_path}` isn't a valid net: {size} bytes don't match any hidden layer size that's a multiple of {HL_ALIGN}{}",
+            if threat_inputs == 0 {
+                ""
+            } else {
+                " with threat inputs"
+            }
+        );
+    }
 
-    fs::copy(in_path, out_path).unwrap();
+    // The embedded net is stored compressed, to keep the binary small.
+    fs::write(out_path, compress::compress(&bytes)).unwrap();
 }
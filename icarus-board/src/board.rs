@@ -2,6 +2,7 @@ use std::{array, fmt, mem};
 
 use icarus_common::{
     bitboard::Bitboard,
+    lookups::between,
     piece::{Color, Piece},
     square::{File, Rank, Square},
     util::enum_map::EnumMap,
@@ -12,7 +13,7 @@ use crate::{
     ep_file::EnPassantFile,
     r#move::{Move, MoveFlag},
     movegen::Abort,
-    zobrist::ZOBRIST,
+    zobrist::{CUCKOO, ZOBRIST},
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -21,6 +22,21 @@ pub enum TerminalState {
     Draw,
 }
 
+/// The state that is lost when making a move, and must be restored to unmake it.
+#[derive(Clone, Copy)]
+pub struct Undo {
+    /// The captured piece, if any. For en passant captures, this is a pawn.
+    pub captured: Option<Piece>,
+    pub(crate) castling_rights: EnumMap<Color, CastlingRights>,
+    pub(crate) en_passant: Option<EnPassantFile>,
+    pub(crate) halfmove_clock: u8,
+    /// The threat bitboards, so that unmaking a move doesn't have to recompute them.
+    pub(crate) pinned: Bitboard,
+    pub(crate) checkers: Bitboard,
+    pub(crate) attacked: Bitboard,
+    pub(crate) check_zones: [Bitboard; 4],
+}
+
 #[derive(Clone, Copy)]
 pub struct Board {
     /// Bitboards per piece type, containing both white and black pieces.
@@ -221,6 +237,23 @@ impl Board {
         piece != Piece::King && self.check_zone(piece).contains(mv.to())
     }
 
+    /// Returns whether the side to move has a reversible move that leads to a board with the
+    /// hash `target`. This is only a pseudolegality check, i.e. the move might leave the king
+    /// in check. `target` must be the hash of a board with the opposite side to move.
+    #[inline]
+    pub fn has_reversible_move_to(&self, target: u64) -> bool {
+        let Some((a, b)) = CUCKOO.lookup(self.hash ^ target) else {
+            return false;
+        };
+        if (between(a, b) & self.occupied()).is_non_empty() {
+            return false;
+        }
+
+        // Since the hashes only differ by the moved piece, exactly one of the squares is occupied.
+        let from = if self.occupied().contains(a) { a } else { b };
+        self.occupied_by(self.stm).contains(from)
+    }
+
     /// Returns whether the given move is legal on the current board. Note that this uses move generation internally, so
     /// it is rather slow. In return, it can handle _any_ kind of move, so it doesn't require any invariants of `Move` to hold.
     #[inline]
@@ -0,0 +1,289 @@
+//! A full game of chess, played from some starting board. Unlike [`Board`], which only knows
+//! about the current position, a [`Game`] keeps the move history, so it can apply every rule
+//! that ends a game: checkmate, stalemate, insufficient material, the 50 and 75 move rules, and
+//! threefold and fivefold repetition.
+//!
+//! Some draws end the game automatically (stalemate, insufficient material, 75 moves, fivefold
+//! repetition), while others only allow a player to claim a draw (50 moves, threefold
+//! repetition). Whether claimable draws are claimed is up to the user of the game.
+
+use icarus_common::piece::Color;
+
+use crate::{board::Board, r#move::Move, movegen::Abort};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WinReason {
+    Checkmate,
+    /// The loser ran out of time.
+    Timeout,
+    /// The game was ended early because a search found a forced mate.
+    MateScore,
+    /// The game was ended early, e.g. because of a decisive search score.
+    Adjudication,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawReason {
+    Stalemate,
+    InsufficientMaterial,
+    /// 100 plies without a capture or pawn move. Must be claimed.
+    FiftyMoves,
+    /// 150 plies without a capture or pawn move. Ends the game automatically.
+    SeventyFiveMoves,
+    /// The position occurred for the third time. Must be claimed.
+    Threefold,
+    /// The position occurred for the fifth time. Ends the game automatically.
+    Fivefold,
+    /// The game was ended early, e.g. because of a drawish search score.
+    Adjudication,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    Win(Color, WinReason),
+    Draw(DrawReason),
+}
+
+impl GameResult {
+    pub fn winner(self) -> Option<Color> {
+        match self {
+            GameResult::Win(color, _) => Some(color),
+            GameResult::Draw(_) => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Game {
+    start: Board,
+    board: Board,
+    moves: Vec<Move>,
+    /// `hashes[i]` is the hash of the board before `moves[i]` was played.
+    hashes: Vec<u64>,
+    result: Option<GameResult>,
+}
+
+impl Game {
+    /// Starts a new game from `board`. If the board is already terminal, the game is over
+    /// right away.
+    pub fn new(board: Board) -> Self {
+        let mut game = Self {
+            start: board,
+            board,
+            moves: vec![],
+            hashes: vec![],
+            result: None,
+        };
+        game.result = game.automatic_result();
+        game
+    }
+
+    pub fn start(&self) -> &Board {
+        &self.start
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    pub fn result(&self) -> Option<GameResult> {
+        self.result
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.result.is_some()
+    }
+
+    /// Plays `mv`, which must be legal, and returns the result if the game ended automatically.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the game is already over.
+    pub fn make_move(&mut self, mv: Move) -> Option<GameResult> {
+        assert!(!self.is_over(), "Made a move in a finished game");
+
+        self.hashes.push(self.board.hash());
+        self.moves.push(mv);
+        self.board.make_move(mv);
+        self.result = self.automatic_result();
+        self.result
+    }
+
+    /// Returns how often the current position has occurred in this game, including right now.
+    pub fn repetitions(&self) -> usize {
+        // Only positions since the last irreversible move with the same side to move can repeat.
+        1 + self
+            .hashes
+            .iter()
+            .rev()
+            .take(self.board.halfmove_clock() as usize)
+            .skip(1)
+            .step_by(2)
+            .filter(|&&hash| hash == self.board.hash())
+            .count()
+    }
+
+    /// Returns the reason for which the side to move may claim a draw, if any.
+    pub fn claimable_draw(&self) -> Option<DrawReason> {
+        if self.is_over() {
+            None
+        } else if self.repetitions() >= 3 {
+            Some(DrawReason::Threefold)
+        } else if self.board.halfmove_clock() >= 100 {
+            Some(DrawReason::FiftyMoves)
+        } else {
+            None
+        }
+    }
+
+    /// Ends the game as a draw if one can be claimed, and returns the result.
+    pub fn claim_draw(&mut self) -> Option<GameResult> {
+        let result = self.claimable_draw().map(GameResult::Draw);
+        if result.is_some() {
+            self.result = result;
+        }
+        result
+    }
+
+    /// Ends the game with the given result, regardless of the position.
+    pub fn adjudicate(&mut self, result: GameResult) {
+        self.result = Some(result);
+    }
+
+    fn automatic_result(&self) -> Option<GameResult> {
+        let any_legal = self.board.gen_moves(|moves| {
+            if !moves.is_empty() {
+                Abort::Yes
+            } else {
+                Abort::No
+            }
+        }) == Abort::Yes;
+
+        // Checkmate takes precedence over the 75 move rule, so we check it first.
+        if !any_legal {
+            Some(if self.board.checkers().is_non_empty() {
+                GameResult::Win(!self.board.stm(), WinReason::Checkmate)
+            } else {
+                GameResult::Draw(DrawReason::Stalemate)
+            })
+        } else if self.board.insufficient_material() {
+            Some(GameResult::Draw(DrawReason::InsufficientMaterial))
+        } else if self.board.halfmove_clock() >= 150 {
+            Some(GameResult::Draw(DrawReason::SeventyFiveMoves))
+        } else if self.repetitions() >= 5 {
+            Some(GameResult::Draw(DrawReason::Fivefold))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_fen(fen: &str, halfmove_clock: u8) -> Game {
+        // FENs with a halfmove clock of 100 or more are rejected, so we set it directly.
+        let mut board = Board::read_fen(fen).unwrap();
+        board.halfmove_clock = halfmove_clock;
+        Game::new(board)
+    }
+
+    fn play(game: &mut Game, lan: &str) -> Option<GameResult> {
+        let mv = game.board().parse_move(lan, false).unwrap();
+        game.make_move(mv)
+    }
+
+    #[test]
+    fn checkmate() {
+        let mut game = Game::new(Board::start_pos());
+        for lan in ["f2f3", "e7e5", "g2g4"] {
+            assert_eq!(play(&mut game, lan), None);
+        }
+
+        let mate = Some(GameResult::Win(Color::Black, WinReason::Checkmate));
+        assert_eq!(play(&mut game, "d8h4"), mate);
+        assert_eq!(game.result(), mate);
+        assert_eq!(game.claimable_draw(), None);
+    }
+
+    #[test]
+    fn stalemate() {
+        let mut game = from_fen("7k/8/6K1/5Q2/8/8/8/8 w - - 0 1", 0);
+        let stalemate = Some(GameResult::Draw(DrawReason::Stalemate));
+        assert_eq!(play(&mut game, "f5f7"), stalemate);
+
+        let game = Game::new(*game.board());
+        assert_eq!(game.result(), stalemate);
+    }
+
+    #[test]
+    fn insufficient_material() {
+        let mut game = from_fen("8/8/8/4k3/8/8/3r4/3KB3 w - - 0 1", 0);
+        assert_eq!(game.result(), None);
+        assert_eq!(
+            play(&mut game, "d1d2"),
+            Some(GameResult::Draw(DrawReason::InsufficientMaterial))
+        );
+    }
+
+    #[test]
+    fn fifty_and_seventy_five_moves() {
+        let mut game = from_fen("4k2r/8/8/8/8/8/8/R3K3 w - - 0 1", 99);
+        assert_eq!(game.claimable_draw(), None);
+
+        // The 50 move rule only allows claiming a draw, the game goes on otherwise.
+        assert_eq!(play(&mut game, "a1a2"), None);
+        assert_eq!(game.claimable_draw(), Some(DrawReason::FiftyMoves));
+        assert_eq!(play(&mut game, "h8h7"), None);
+        assert_eq!(game.result(), None);
+
+        let mut claimed = game.clone();
+        let fifty = Some(GameResult::Draw(DrawReason::FiftyMoves));
+        assert_eq!(claimed.claim_draw(), fifty);
+        assert_eq!(claimed.result(), fifty);
+
+        // The 75 move rule ends the game without a claim.
+        let mut game = from_fen("4k2r/8/8/8/8/8/8/R3K3 w - - 0 1", 149);
+        assert_eq!(game.result(), None);
+        assert_eq!(
+            play(&mut game, "a1a2"),
+            Some(GameResult::Draw(DrawReason::SeventyFiveMoves))
+        );
+    }
+
+    #[test]
+    fn checkmate_before_seventy_five_moves() {
+        let mut game = from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 149);
+        assert_eq!(
+            play(&mut game, "a1a8"),
+            Some(GameResult::Win(Color::White, WinReason::Checkmate))
+        );
+        assert_eq!(game.board().halfmove_clock(), 150);
+    }
+
+    #[test]
+    fn repetition_draws() {
+        let mut game = Game::new(Board::start_pos());
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+        for (i, lan) in shuffle.iter().cycle().take(16).enumerate() {
+            assert_eq!(game.result(), None);
+            let mv = game.board().parse_move(lan, false).unwrap();
+            game.make_move(mv);
+
+            // The start position occurs again after every 4 plies.
+            let claimable = (i + 1 >= 8).then_some(DrawReason::Threefold);
+            if (i + 1) % 4 == 0 && i + 1 < 16 {
+                assert_eq!(game.claimable_draw(), claimable);
+            }
+        }
+
+        assert_eq!(game.repetitions(), 5);
+        assert_eq!(game.result(), Some(GameResult::Draw(DrawReason::Fivefold)));
+    }
+}
@@ -4,6 +4,7 @@ pub mod attack_generators;
 pub mod board;
 pub mod castling;
 pub mod ep_file;
+pub mod game;
 pub mod is_legal;
 pub mod r#move;
 pub mod movegen;
@@ -11,7 +11,7 @@ use icarus_common::{
 
 use crate::{
     attack_generators::{bishop_moves, rook_moves},
-    board::Board,
+    board::{Board, Undo},
     castling::CastlingDirection,
     ep_file::EnPassantFile,
     r#move::{Move, MoveFlag, PieceMoves},
@@ -550,10 +550,42 @@ impl Board {
         }
     }
 
+    fn undo(&self, captured: Option<Piece>) -> Undo {
+        Undo {
+            captured,
+            castling_rights: self.castling_rights,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            pinned: self.pinned,
+            checkers: self.checkers,
+            attacked: self.attacked,
+            check_zones: self.check_zones,
+        }
+    }
+
+    /// Restores the state saved in `undo`, and flips the side to move back.
+    fn restore(&mut self, undo: &Undo) {
+        self.stm = !self.stm;
+        self.hash ^= ZOBRIST.black_to_move;
+        self.fullmove_count -= u16::from(self.stm == Color::Black);
+        self.halfmove_clock = undo.halfmove_clock;
+        self.pinned = undo.pinned;
+        self.checkers = undo.checkers;
+        self.attacked = undo.attacked;
+        self.check_zones = undo.check_zones;
+        self.set_en_passant(undo.en_passant);
+        for color in [Color::White, Color::Black] {
+            for dir in [CastlingDirection::Long, CastlingDirection::Short] {
+                self.set_castles(color, dir, undo.castling_rights[color].get(dir));
+            }
+        }
+    }
+
     /// Makes the given move on the board. Does *not* check whether the move is legal. An illegal
-    /// move may break the board, silently or loudly.
+    /// move may break the board, silently or loudly. The returned `Undo` can be passed to
+    /// `unmake_move` to take the move back.
     #[inline]
-    pub fn make_move(&mut self, mov: Move) {
+    pub fn make_move(&mut self, mov: Move) -> Undo {
         let (from, to, flag, promotion) = (
             mov.from(),
             mov.to(),
@@ -562,6 +594,12 @@ impl Board {
         );
         debug_assert_ne!(from, to);
 
+        let undo = self.undo(match flag {
+            MoveFlag::Castle => None,
+            MoveFlag::EnPassant => Some(Piece::Pawn),
+            _ => self.piece_on(to),
+        });
+
         self.halfmove_clock += 1;
         self.fullmove_count += u16::from(self.stm == Color::Black);
 
@@ -675,12 +713,78 @@ impl Board {
             self.calc_ep_file(ep);
         }
         self.calc_threats();
+
+        undo
     }
 
+    /// Takes back `mov`, which must be the last move made on this board, using the `Undo`
+    /// returned by `make_move`.
     #[inline]
-    pub fn make_null_move(&mut self) {
+    pub fn unmake_move(&mut self, mov: Move, undo: &Undo) {
+        let (from, to, flag) = (mov.from(), mov.to(), mov.flag());
+
+        self.restore(undo);
+        let us = self.stm;
+
+        match flag {
+            MoveFlag::None | MoveFlag::Promotion => {
+                let piece = self.piece_on(to).expect("Move to empty square");
+                let orig = if flag == MoveFlag::Promotion {
+                    Piece::Pawn
+                } else {
+                    piece
+                };
+
+                self.toggle_square(to, us, piece);
+                self.toggle_square(from, us, orig);
+                self.mailbox[to] = None;
+                self.mailbox[from] = Some(orig);
+
+                if let Some(victim) = undo.captured {
+                    self.toggle_square(to, !us, victim);
+                    self.mailbox[to] = Some(victim);
+                }
+            }
+            MoveFlag::Castle => {
+                let dir = if to.file() < from.file() {
+                    CastlingDirection::Long
+                } else {
+                    CastlingDirection::Short
+                };
+
+                let rook_from = to;
+                let king_to = Square::new(dir.king_dst(), from.rank());
+                let rook_to = Square::new(dir.rook_dst(), from.rank());
+
+                self.toggle_square(rook_to, us, Piece::Rook);
+                self.toggle_square(rook_from, us, Piece::Rook);
+                self.toggle_square(king_to, us, Piece::King);
+                self.toggle_square(from, us, Piece::King);
+
+                // Same as in `make_move`, we need to first clear both entries and then set both.
+                self.mailbox[rook_to] = None;
+                self.mailbox[king_to] = None;
+                self.mailbox[rook_from] = Some(Piece::Rook);
+                self.mailbox[from] = Some(Piece::King);
+            }
+            MoveFlag::EnPassant => {
+                let target_sq = Square::new(to.file(), from.rank());
+                self.toggle_square(to, us, Piece::Pawn);
+                self.toggle_square(from, us, Piece::Pawn);
+                self.toggle_square(target_sq, !us, Piece::Pawn);
+
+                self.mailbox[to] = None;
+                self.mailbox[from] = Some(Piece::Pawn);
+                self.mailbox[target_sq] = Some(Piece::Pawn);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn make_null_move(&mut self) -> Undo {
         debug_assert!(self.checkers.is_empty());
 
+        let undo = self.undo(None);
         self.halfmove_clock = (self.halfmove_clock + 1).min(100);
         self.fullmove_count += u16::from(self.stm == Color::Black);
 
@@ -688,5 +792,12 @@ impl Board {
         self.stm = !self.stm;
         self.hash ^= ZOBRIST.black_to_move;
         self.calc_threats();
+
+        undo
+    }
+
+    #[inline]
+    pub fn unmake_null_move(&mut self, undo: &Undo) {
+        self.restore(undo);
     }
 }
@@ -29,6 +29,8 @@ pub fn perft<const BULK: bool>(board: &Board, depth: u8) -> u64 {
 
 #[cfg(test)]
 mod tests {
+    use icarus_common::piece::Color;
+
     use super::*;
 
     macro_rules! perft_test {
@@ -142,6 +144,52 @@ mod tests {
         731511256
     );
 
+    /// Walks the move tree to `depth`, checking that unmaking every move restores the board.
+    fn check_unmake(board: &mut Board, depth: u8) {
+        if depth == 0 {
+            return;
+        }
+
+        let moves: Vec<_> = board.gen_all_moves_to();
+        for mv in moves {
+            let before = *board;
+            let undo = board.make_move(mv);
+            check_unmake(board, depth - 1);
+            board.unmake_move(mv, &undo);
+
+            assert_eq!(board.fen(true), before.fen(true));
+            assert_eq!(board.hash(), before.hash());
+            assert_eq!(board.pawn_hash(), before.pawn_hash());
+            assert_eq!(board.minor_hash(), before.minor_hash());
+            assert_eq!(board.major_hash(), before.major_hash());
+            assert_eq!(
+                board.nonpawn_hash(Color::White),
+                before.nonpawn_hash(Color::White)
+            );
+            assert_eq!(
+                board.nonpawn_hash(Color::Black),
+                before.nonpawn_hash(Color::Black)
+            );
+            assert!(board.checkers() == before.checkers());
+            assert!(board.pinned() == before.pinned());
+            assert!(board.attacked() == before.attacked());
+        }
+    }
+
+    #[test]
+    fn unmake_restores_board() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "1rqbkrbn/1ppppp1p/1n6/p1N3p1/8/2P4P/PP1PPPP1/1RQBKRBN w FBfb - 0 9",
+            "rkb2bnr/pp2pppp/2p1n3/3p4/q2P4/5NP1/PPP1PP1P/RKBNQBR1 w Aha - 0 9",
+        ] {
+            check_unmake(&mut Board::read_fen(fen).unwrap(), 3);
+        }
+    }
+
     #[cfg(feature = "perft-all-960")]
     include!(concat!(env!("OUT_DIR"), "/perft_generated.rs"));
 }
@@ -88,3 +88,113 @@ pub static ZOBRIST: Zobrist = {
 
     zobrist
 };
+
+/// Cuckoo hash table of all reversible (non-pawn) moves on an empty board, keyed by the
+/// difference they make to the zobrist hash. Used to detect upcoming repetitions, following
+/// <https://web.archive.org/web/20201107002606/https://marcelk.net/2013-04-06/paper/upcoming-rep-v2.pdf>.
+pub struct Cuckoo {
+    keys: [u64; CUCKOO_SIZE],
+    moves: [Option<(Square, Square)>; CUCKOO_SIZE],
+}
+
+const CUCKOO_SIZE: usize = 8192;
+
+const fn cuckoo_h1(key: u64) -> usize {
+    (key as usize) & (CUCKOO_SIZE - 1)
+}
+
+const fn cuckoo_h2(key: u64) -> usize {
+    ((key >> 16) as usize) & (CUCKOO_SIZE - 1)
+}
+
+impl Cuckoo {
+    /// Returns the squares of the reversible move whose hash difference is `key`, if any.
+    /// The squares are unordered, i.e. the move may go in either direction.
+    #[inline]
+    pub fn lookup(&self, key: u64) -> Option<(Square, Square)> {
+        [cuckoo_h1(key), cuckoo_h2(key)]
+            .into_iter()
+            .find(|&i| self.keys[i] == key)
+            .and_then(|i| self.moves[i])
+    }
+}
+
+pub static CUCKOO: Cuckoo = {
+    let mut cuckoo = Cuckoo {
+        keys: [0; CUCKOO_SIZE],
+        moves: [None; CUCKOO_SIZE],
+    };
+
+    let mut c = 0;
+    while c < Color::COUNT {
+        // Pawn moves are never reversible.
+        let mut p = Piece::Knight as usize;
+        while p < Piece::COUNT {
+            let mut a = 0;
+            while a < Square::COUNT {
+                let mut b = a + 1;
+                while b < Square::COUNT {
+                    let (sa, sb) = (Square::from_idx(a as u8), Square::from_idx(b as u8));
+                    let df = (sa.file().idx() as i8 - sb.file().idx() as i8).abs();
+                    let dr = (sa.rank().idx() as i8 - sb.rank().idx() as i8).abs();
+
+                    let orth = df == 0 || dr == 0;
+                    let diag = df == dr;
+                    let reachable = match Piece::from_idx(p as u8) {
+                        Piece::Knight => (df == 1 && dr == 2) || (df == 2 && dr == 1),
+                        Piece::Bishop => diag,
+                        Piece::Rook => orth,
+                        Piece::Queen => diag || orth,
+                        Piece::King => df <= 1 && dr <= 1,
+                        Piece::Pawn => false,
+                    };
+
+                    if reachable {
+                        let (col, pt) = (Color::from_idx(c as u8), Piece::from_idx(p as u8));
+                        let mut key = ZOBRIST.piece(sa, pt, col)
+                            ^ ZOBRIST.piece(sb, pt, col)
+                            ^ ZOBRIST.black_to_move;
+                        let mut mv = Some((sa, sb));
+                        let mut i = cuckoo_h1(key);
+
+                        // Insert the move, displacing the existing entries until one lands in an
+                        // empty slot.
+                        loop {
+                            let (old_key, old_mv) = (cuckoo.keys[i], cuckoo.moves[i]);
+                            cuckoo.keys[i] = key;
+                            cuckoo.moves[i] = mv;
+                            (key, mv) = (old_key, old_mv);
+
+                            if mv.is_none() {
+                                break;
+                            }
+                            i = if i == cuckoo_h1(key) {
+                                cuckoo_h2(key)
+                            } else {
+                                cuckoo_h1(key)
+                            };
+                        }
+                    }
+                    b += 1;
+                }
+                a += 1;
+            }
+            p += 1;
+        }
+        c += 1;
+    }
+
+    cuckoo
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cuckoo_move_count() {
+        // 2 colors * (168 knight + 280 bishop + 448 rook + 728 queen + 210 king) moves
+        let n = CUCKOO.moves.iter().filter(|mv| mv.is_some()).count();
+        assert_eq!(n, 3668);
+    }
+}
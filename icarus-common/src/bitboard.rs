@@ -8,7 +8,7 @@ use std::{
 };
 
 #[repr(transparent)]
-#[derive(Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct Bitboard(pub u64);
 
 impl Bitboard {
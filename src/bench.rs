@@ -1,4 +1,7 @@
 use std::{
+    fs,
+    hint::black_box,
+    path::{Path, PathBuf},
     sync::atomic::Ordering,
     time::{Duration, Instant},
 };
@@ -7,10 +10,12 @@ use icarus_board::board::Board;
 
 use crate::{
     engine::Engine,
+    nnue::network::Nnue,
     position::Position,
     search::{
-        searcher::{Print, Searcher},
+        searcher::{MAX_THREADS, Print, Searcher},
         time_manager::DEFAULT_MOVE_OVERHEAD,
+        transposition_table::{DEFAULT_TT_SIZE, MAX_TT_SIZE},
     },
     uci::SearchLimit,
 };
@@ -69,33 +74,366 @@ pub static FENS: [&str; 50] = [
 ];
 
 pub const DEFAULT_BENCH_DEPTH: u8 = 13;
+pub const DEFAULT_NNUE_BENCH_DEPTH: u8 = 3;
+
+/// Settings of the `bench` command. The defaults are what OpenBench expects.
+#[derive(Debug, Clone)]
+pub struct BenchConfig {
+    /// Either a depth, node or movetime limit, used for every position.
+    pub limit: SearchLimit,
+    pub threads: u32,
+    /// Transposition table size in MiB.
+    pub hash: u64,
+    /// FEN or EPD file with the positions to search, instead of `FENS`.
+    pub file: Option<PathBuf>,
+    /// Print the results of each position.
+    pub verbose: bool,
+    /// Print a JSON summary instead of the usual output.
+    pub json: bool,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        Self {
+            limit: SearchLimit::Depth(DEFAULT_BENCH_DEPTH as _),
+            threads: 1,
+            hash: DEFAULT_TT_SIZE,
+            file: None,
+            verbose: false,
+            json: false,
+        }
+    }
+}
+
+struct BenchResult {
+    fen: String,
+    nodes: u64,
+    duration: Duration,
+}
+
+/// Reads positions from a file with one FEN or EPD per line. EPD lines don't have move
+/// counters, so they start at zero.
+pub fn read_positions(path: &Path) -> Result<Vec<Board>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read `{}`: {e}", path.display()))?;
+
+    let mut boards = vec![];
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let counters_given =
+            parts.len() >= 6 && parts[4].parse::<u16>().is_ok() && parts[5].parse::<u16>().is_ok();
+
+        let fen = if counters_given {
+            parts[..6].join(" ")
+        } else {
+            format!("{} 0 1", parts[..parts.len().min(4)].join(" "))
+        };
+        let board = Board::read_fen(&fen).ok_or_else(|| format!("Invalid position `{line}`"))?;
+        boards.push(board);
+    }
+
+    if boards.is_empty() {
+        return Err(format!("`{}` has no positions", path.display()));
+    }
+
+    Ok(boards)
+}
 
 impl Engine {
-    pub fn bench(&self, depth: u8, _cli: bool) {
-        let mut searcher = Searcher::default();
-        let limits = vec![SearchLimit::Depth(depth as _)];
+    pub fn bench(&self, config: &BenchConfig) {
+        if !(1..=MAX_THREADS).contains(&config.threads) || !(1..=MAX_TT_SIZE).contains(&config.hash)
+        {
+            println!("info string Invalid bench threads or hash");
+            return;
+        }
 
-        let mut duration = Duration::ZERO;
-        let mut nodes = 0;
+        let boards = match &config.file {
+            Some(path) => match read_positions(path) {
+                Ok(boards) => boards,
+                Err(e) => {
+                    println!("info string {e}");
+                    return;
+                }
+            },
+            None => FENS
+                .iter()
+                .map(|fen| Board::read_fen(fen).unwrap())
+                .collect(),
+        };
 
-        for fen in FENS {
-            let pos = Position::new(Board::read_fen(fen).unwrap());
+        let mut searcher = Searcher::default();
+        if config.hash != DEFAULT_TT_SIZE {
+            searcher.resize_ttable(config.hash);
+        }
+        if config.threads != 1 {
+            searcher.change_threads(config.threads);
+        }
+
+        let mut results = Vec::with_capacity(boards.len());
+        for (i, board) in boards.into_iter().enumerate() {
+            let fen = board.fen(self.chess960);
             let start = Instant::now();
             searcher.search(
-                pos,
-                limits.clone(),
-                false,
+                Position::new(board),
+                vec![config.limit.clone()],
                 false,
+                self.chess960,
                 DEFAULT_MOVE_OVERHEAD,
                 Print::None,
             );
             searcher.wait();
-            duration += start.elapsed();
-            nodes += searcher.global_ctx.nodes.load(Ordering::Relaxed);
+
+            let result = BenchResult {
+                fen,
+                nodes: searcher.global_ctx.nodes.load(Ordering::Relaxed),
+                duration: start.elapsed(),
+            };
+            if config.verbose && !config.json {
+                println!(
+                    "info string position {} nodes {} time {} nps {} fen {}",
+                    i + 1,
+                    result.nodes,
+                    result.duration.as_millis(),
+                    nps(result.nodes, result.duration),
+                    result.fen,
+                );
+            }
+            results.push(result);
+        }
+
+        let nodes = results.iter().map(|r| r.nodes).sum();
+        let duration = results.iter().map(|r| r.duration).sum();
+        if config.json {
+            println!("{}", json_summary(config, &results, nodes, duration));
+        } else {
+            println!("info string {:.2?}", duration);
+            println!("{nodes} nodes {} nps", nps(nodes, duration));
+        }
+    }
+}
+
+/// Number of plies of the lines played from every leaf in the `lines` part of `nnue_bench`.
+const NNUE_BENCH_LINE: u8 = 6;
+
+/// Measures the speed of the accumulator updates, with and without fusing the updates of several
+/// moves. The move tree of every bench position is walked up to `depth` plies, and only the leaves
+/// are evaluated, like after interior nodes that are cut off or pruned before their static eval.
+/// Since fused updates only matter when several moves were made since the last eval, the tree is
+/// walked again with a line of `NNUE_BENCH_LINE` plies played from every leaf, which is only
+/// evaluated at its end.
+pub fn nnue_bench(depth: u8) {
+    type Update = fn(&mut Nnue, &Board);
+
+    fn walk(board: &Board, nnue: &mut Nnue, depth: u8, line: u8, update: Update) -> (u64, i64) {
+        let moves: Vec<_> = board.gen_all_moves_to();
+        if (depth == 0 && line == 0) || moves.is_empty() {
+            update(nnue, board);
+            return (1, black_box(nnue.eval(board.stm())) as i64);
+        }
+
+        // Lines always pick the same move for the same position, so that both runs match.
+        let moves = if depth == 0 {
+            &moves[line as usize * 7 % moves.len()..][..1]
+        } else {
+            &moves[..]
+        };
+
+        let (mut evals, mut sum) = (0, 0);
+        for &mv in moves {
+            let mut child = *board;
+            nnue.make_move(board, mv);
+            child.make_move(mv);
+            let (e, s) = match depth {
+                0 => walk(&child, nnue, 0, line - 1, update),
+                _ => walk(&child, nnue, depth - 1, line, update),
+            };
+            nnue.unmake_move();
+            evals += e;
+            sum += s;
         }
+        (evals, sum)
+    }
+
+    let boards: Vec<Board> = FENS
+        .iter()
+        .map(|fen| Board::read_fen(fen).unwrap())
+        .collect();
+    let mut nnue = Nnue::new(&boards[0]);
+
+    let walks = [
+        ("leaves", depth, 0),
+        ("lines", depth.saturating_sub(1), NNUE_BENCH_LINE),
+    ];
+    let updates: [(&str, Update); 2] =
+        [("stepwise", Nnue::update_stepwise), ("fused", Nnue::update)];
+    for (walk_name, depth, line) in walks {
+        for (update_name, update) in updates {
+            let (mut evals, mut sum) = (0, 0);
+            let start = Instant::now();
+            for board in &boards {
+                nnue.full_reset(board);
+                let (e, s) = walk(board, &mut nnue, depth, line, update);
+                evals += e;
+                sum += s;
+            }
+            let duration = start.elapsed();
+
+            // The eval sum only differs between the two updates if one of them is broken.
+            println!(
+                "{walk_name:<6} {update_name:<8} {evals:>9} evals {:>6} ms {:>8} evals/s (eval sum {sum})",
+                duration.as_millis(),
+                nps(evals, duration),
+            );
+        }
+    }
+}
+
+fn nps(nodes: u64, duration: Duration) -> u64 {
+    ((nodes as f64) / (duration.as_micros().max(1) as f64) * 1e6) as u64
+}
+
+/// Formats the results as a single line of JSON. FENs never contain characters that would need
+/// escaping, so no JSON library is needed.
+fn json_summary(
+    config: &BenchConfig,
+    results: &[BenchResult],
+    nodes: u64,
+    duration: Duration,
+) -> String {
+    let limit = match config.limit {
+        SearchLimit::Depth(depth) => format!(r#"{{"depth":{depth}}}"#),
+        SearchLimit::Nodes(nodes) => format!(r#"{{"nodes":{nodes}}}"#),
+        SearchLimit::MoveTime(ms) => format!(r#"{{"movetime":{ms}}}"#),
+        _ => unreachable!("bench only supports depth, node and movetime limits"),
+    };
+    let positions: Vec<String> = results
+        .iter()
+        .map(|r| {
+            format!(
+                r#"{{"fen":"{}","nodes":{},"time_ms":{},"nps":{}}}"#,
+                r.fen,
+                r.nodes,
+                r.duration.as_millis(),
+                nps(r.nodes, r.duration),
+            )
+        })
+        .collect();
+
+    format!(
+        r#"{{"version":"{}","threads":{},"hash":{},"limit":{limit},"nodes":{nodes},"time_ms":{},"nps":{},"positions":[{}]}}"#,
+        env!("CARGO_PKG_VERSION"),
+        config.threads,
+        config.hash,
+        duration.as_millis(),
+        nps(nodes, duration),
+        positions.join(","),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, time::Duration};
+
+    use crate::{
+        bench::{BenchConfig, BenchResult, json_summary, read_positions},
+        uci::SearchLimit,
+    };
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    fn positions_file(name: &str, text: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("icarus-{}-{name}", std::process::id()));
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    fn read(name: &str, text: &str) -> Result<Vec<String>, String> {
+        let path = positions_file(name, text);
+        let result = read_positions(&path);
+        fs::remove_file(path).unwrap();
+        result.map(|boards| boards.iter().map(|b| b.fen(false)).collect())
+    }
+
+    #[test]
+    fn read_fens_and_epds() {
+        let text = format!(
+            "{START}\n\
+             r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - bm e2a6; id \"kiwipete\";\n\
+             8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 12 40\n"
+        );
+        assert_eq!(
+            read("mixed.epd", &text).unwrap(),
+            [
+                START,
+                KIWIPETE,
+                "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 12 40"
+            ]
+        );
+    }
+
+    #[test]
+    fn read_fens_without_counters() {
+        let text = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -\n\
+                    r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -";
+        assert_eq!(read("no-counters.fen", text).unwrap(), [START, KIWIPETE]);
+    }
+
+    #[test]
+    fn read_skips_blank_lines() {
+        let text = format!("\n{START}\n   \n\n  {KIWIPETE}  \n\n");
+        assert_eq!(read("blank.fen", &text).unwrap(), [START, KIWIPETE]);
+    }
+
+    #[test]
+    fn read_rejects_invalid_positions() {
+        let text = format!("{START}\nnot a position\n{KIWIPETE}\n");
+        let err = read("invalid.fen", &text).unwrap_err();
+        assert!(err.contains("not a position"), "{err}");
+
+        let text = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1";
+        assert!(read("bad-stm.fen", text).is_err());
+
+        assert!(read("empty.fen", "\n  \n").is_err());
+    }
+
+    #[test]
+    fn json_summary_is_valid_json() {
+        let config = BenchConfig {
+            limit: SearchLimit::Nodes(5000),
+            threads: 2,
+            hash: 32,
+            ..BenchConfig::default()
+        };
+        let results = [
+            BenchResult {
+                fen: START.to_string(),
+                nodes: 5000,
+                duration: Duration::from_millis(10),
+            },
+            BenchResult {
+                fen: KIWIPETE.to_string(),
+                nodes: 5002,
+                duration: Duration::from_millis(20),
+            },
+        ];
+
+        let json = json_summary(&config, &results, 10002, Duration::from_millis(30));
+        let summary: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(summary["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(summary["threads"], 2);
+        assert_eq!(summary["hash"], 32);
+        assert_eq!(summary["limit"]["nodes"], 5000);
+        assert_eq!(summary["nodes"], 10002);
+        assert_eq!(summary["time_ms"], 30);
+        assert_eq!(summary["nps"], 333400);
 
-        let nps = ((nodes as f64) / (duration.as_micros().max(1) as f64) * 1e6) as u64;
-        println!("info string {:.2?}", duration);
-        println!("{nodes} nodes {nps} nps")
+        let positions = summary["positions"].as_array().unwrap();
+        assert_eq!(positions.len(), 2);
+        assert_eq!(positions[1]["fen"], KIWIPETE);
+        assert_eq!(positions[1]["nodes"], 5002);
+        assert_eq!(positions[1]["time_ms"], 20);
+        assert_eq!(positions[1]["nps"], 250100);
     }
 }
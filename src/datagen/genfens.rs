@@ -10,8 +10,10 @@ use rand::{Rng, SeedableRng, rngs::SmallRng};
 
 use crate::{
     position::Position,
+    score::Score,
     search::{
         move_picker::MAX_MOVES,
+        params::Params,
         searcher::{GlobalCtx, Print, SearchParams, ThreadCtx},
         transposition_table::{DEFAULT_TT_SIZE, TTable},
     },
@@ -66,12 +68,21 @@ pub fn try_generate_pos(
         true,
         false,
         0,
+        0,
+        &Params::DEFAULT,
     );
     let score = thread_ctxs[stm].do_search(SearchParams {
         pos,
         root_moves: None,
         chess960: dfrc,
         print_info: Print::None,
+        debug: false,
+        trace: Default::default(),
+        deterministic: false,
+        skill: Default::default(),
+        contempt: Score::ZERO,
+        wdl_contempt: Score::ZERO,
+        params: Params::DEFAULT,
     });
 
     let limit = 1000;
@@ -90,6 +101,10 @@ pub fn genfens(n: usize, seed: u64, dfrc: bool, random_moves: usize) {
                 nodes: Default::default(),
                 num_searching: Default::default(),
                 ttable: TTable::new(DEFAULT_TT_SIZE),
+                stats: Default::default(),
+                turnstile: Default::default(),
+                results: Default::default(),
+                last_result: Default::default(),
             });
             ThreadCtx::new(global, 0, dfrc, false)
         }
@@ -11,7 +11,10 @@ use std::{
 };
 
 use clap::{Args, Parser};
-use icarus_board::{board::TerminalState, r#move::MoveFlag, movegen::Abort};
+use icarus_board::{
+    game::{DrawReason, Game, GameResult, WinReason},
+    r#move::MoveFlag,
+};
 use icarus_common::{
     piece::Color,
     util::enum_map::{EnumMap, enum_map},
@@ -25,7 +28,7 @@ use viriformat::{
         piece::PieceType as ViriPiece,
         types::Square as ViriSquare,
     },
-    dataformat::Game,
+    dataformat::Game as ViriGame,
 };
 
 use crate::{
@@ -33,6 +36,7 @@ use crate::{
     position::Position,
     score::Score,
     search::{
+        params::Params,
         searcher::{GlobalCtx, Print, SearchParams, ThreadCtx},
         transposition_table::{DEFAULT_TT_SIZE, TTable},
     },
@@ -196,6 +200,10 @@ fn worker_loop(ctx: &DatagenCtx, tx: Sender<Vec<u8>>) {
                 nodes: Default::default(),
                 num_searching: Default::default(),
                 ttable: TTable::new(DEFAULT_TT_SIZE),
+                stats: Default::default(),
+                turnstile: Default::default(),
+                results: Default::default(),
+                last_result: Default::default(),
             });
             ThreadCtx::new(global, 0, ctx.dfrc, false)
         }
@@ -239,7 +247,7 @@ fn play_game(
     rng: &mut SmallRng,
     ctx: &DatagenCtx,
     thread_ctxs: &mut EnumMap<Color, ThreadCtx>,
-) -> Game {
+) -> ViriGame {
     let mut pos = Position::new(
         std::iter::repeat_with(|| try_generate_pos(rng, ctx.dfrc, ctx.random_moves, thread_ctxs))
             .flatten()
@@ -252,12 +260,13 @@ fn play_game(
         t.global.ttable.clear();
     });
 
-    let mut game = {
+    let mut game = Game::new(*pos.board());
+    let mut viri_game = {
         let mut board = ViriBoard::new();
         board
             .set_from_fen(&pos.board().fen(ctx.dfrc), ctx.dfrc)
             .unwrap();
-        Game::new(&board)
+        ViriGame::new(&board)
     };
 
     let mut prev_score: Option<Score> = None;
@@ -274,6 +283,8 @@ fn play_game(
             true,
             false,
             0,
+            0,
+            &Params::DEFAULT,
         );
 
         let score = thread_ctxs[stm].do_search(SearchParams {
@@ -281,6 +292,13 @@ fn play_game(
             root_moves: None,
             chess960: ctx.dfrc,
             print_info: Print::None,
+            debug: false,
+            trace: Default::default(),
+            deterministic: false,
+            skill: Default::default(),
+            contempt: Score::ZERO,
+            wdl_contempt: Score::ZERO,
+            params: Params::DEFAULT,
         });
         let mv = thread_ctxs[stm].search_stack[0].pv[0];
 
@@ -302,8 +320,9 @@ fn play_game(
 
         let white_eval = score * stm.signum() as i16;
 
-        game.add_move(viri_mv, white_eval.0);
+        viri_game.add_move(viri_mv, white_eval.0);
         pos.make_move(mv, None);
+        game.make_move(mv);
 
         if let Some(prev) = prev_score {
             if white_eval.0.abs() >= ctx.win_adj_score && white_eval.0.signum() == prev.0.signum() {
@@ -313,7 +332,7 @@ fn play_game(
             }
         }
 
-        if game.moves.len().div_ceil(2) >= ctx.draw_adj_movenumber
+        if viri_game.moves.len().div_ceil(2) >= ctx.draw_adj_movenumber
             && white_eval.0.abs() <= ctx.draw_adj_score
         {
             draw_adj_count += 1;
@@ -323,65 +342,54 @@ fn play_game(
 
         prev_score = Some(white_eval);
 
-        if white_eval.is_mate() {
-            if white_eval.0 > 0 {
-                break GameOutcome::WhiteWin(WinType::Mate);
+        if !game.is_over() {
+            let winner = if white_eval.0 > 0 {
+                Color::White
             } else {
-                break GameOutcome::BlackWin(WinType::Mate);
-            }
-        }
-
-        if win_adj_count >= ctx.win_adj_movecount * 2 {
-            if white_eval.0 > 0 {
-                break GameOutcome::WhiteWin(WinType::Adjudication);
+                Color::Black
+            };
+
+            if white_eval.is_mate() {
+                game.adjudicate(GameResult::Win(winner, WinReason::MateScore));
+            } else if win_adj_count >= ctx.win_adj_movecount * 2 {
+                game.adjudicate(GameResult::Win(winner, WinReason::Adjudication));
+            } else if draw_adj_count >= ctx.draw_adj_movecount * 2 {
+                game.adjudicate(GameResult::Draw(DrawReason::Adjudication));
             } else {
-                break GameOutcome::BlackWin(WinType::Adjudication);
+                game.claim_draw();
             }
         }
 
-        if draw_adj_count >= ctx.draw_adj_movecount * 2 {
-            break GameOutcome::Draw(DrawType::Adjudication);
+        if let Some(result) = game.result() {
+            break result;
         }
+    };
 
-        match pos.board().terminal_state() {
-            Some(TerminalState::Checkmate(stm)) => match stm {
-                Color::White => break GameOutcome::WhiteWin(WinType::Mate),
-                Color::Black => break GameOutcome::BlackWin(WinType::Mate),
-            },
-            Some(TerminalState::Draw) => {
-                if pos.board().gen_moves(|moves| {
-                    if !moves.is_empty() {
-                        Abort::Yes
-                    } else {
-                        Abort::No
-                    }
-                }) == Abort::Yes
-                {
-                    break GameOutcome::Draw(DrawType::InsufficientMaterial);
-                } else {
-                    break GameOutcome::Draw(DrawType::Stalemate);
-                }
-            }
-            None => {}
-        }
+    match result.winner() {
+        Some(Color::White) => ctx.white_wins.fetch_add(1, Relaxed),
+        Some(Color::Black) => ctx.black_wins.fetch_add(1, Relaxed),
+        None => ctx.draws.fetch_add(1, Relaxed),
+    };
 
-        if pos.board().halfmove_clock() >= 100 {
-            break GameOutcome::Draw(DrawType::FiftyMoves);
-        }
+    viri_game.set_outcome(viri_outcome(result));
+    viri_game
+}
+
+fn viri_outcome(result: GameResult) -> GameOutcome {
+    let win_type = |reason| match reason {
+        WinReason::Checkmate | WinReason::MateScore => WinType::Mate,
+        WinReason::Timeout | WinReason::Adjudication => WinType::Adjudication,
     };
 
     match result {
-        GameOutcome::WhiteWin(_) => {
-            ctx.white_wins.fetch_add(1, Relaxed);
-        }
-        GameOutcome::BlackWin(_) => {
-            ctx.black_wins.fetch_add(1, Relaxed);
-        }
-        _ => {
-            ctx.draws.fetch_add(1, Relaxed);
-        }
+        GameResult::Win(Color::White, reason) => GameOutcome::WhiteWin(win_type(reason)),
+        GameResult::Win(Color::Black, reason) => GameOutcome::BlackWin(win_type(reason)),
+        GameResult::Draw(reason) => GameOutcome::Draw(match reason {
+            DrawReason::Stalemate => DrawType::Stalemate,
+            DrawReason::InsufficientMaterial => DrawType::InsufficientMaterial,
+            DrawReason::FiftyMoves | DrawReason::SeventyFiveMoves => DrawType::FiftyMoves,
+            DrawReason::Threefold | DrawReason::Fivefold => DrawType::Repetition,
+            DrawReason::Adjudication => DrawType::Adjudication,
+        }),
     }
-
-    game.set_outcome(result);
-    game
 }
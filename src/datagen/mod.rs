@@ -1,3 +1,7 @@
 pub mod genfens;
 #[cfg(feature = "datagen")]
 pub mod internal;
+#[cfg(feature = "datagen")]
+pub mod texel;
+#[cfg(feature = "datagen")]
+pub mod wdlfit;
@@ -0,0 +1,543 @@
+//! The `texel` subcommand, which refits the PeSTO tables from `pesto.rs` to viriformat games
+//! written by `datagen`. Quiet positions (not in check, and the move played from them wasn't
+//! tactical) are extracted from the games, and the middlegame/endgame piece-square tables and
+//! the phase weights are fitted by gradient descent on the squared error between the game
+//! result and a sigmoid of the eval.
+//!
+//! The sigmoid scale is fitted to the current tables first and kept fixed afterwards, so the
+//! tuned tables stay in the same units as the old ones.
+
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    io::{BufReader, ErrorKind},
+    num::NonZero,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use clap::{Args, Parser};
+use icarus_board::board::Board;
+use icarus_common::piece::{Color, Piece};
+use viriformat::dataformat::{Game as ViriGame, WDL};
+
+use crate::pesto::{self, PESTO, PHASE_INC};
+
+/// Number of piece-square table entries per phase.
+const PSQT_SIZE: usize = 6 * 64;
+/// Offset of the endgame tables in the parameter vector.
+const EG: usize = PSQT_SIZE;
+/// Offset of the phase weights in the parameter vector.
+const PHASE: usize = 2 * PSQT_SIZE;
+const NUM_PARAMS: usize = PHASE + 6;
+
+/// Number of pieces of each type in the start position, which has the maximum phase.
+const START_COUNTS: [f64; 6] = [16.0, 4.0, 4.0, 4.0, 2.0, 2.0];
+/// Total phase of the start position in `pesto::eval`.
+const MAX_PHASE: f64 = 24.0;
+
+/// The phase weights are much smaller than the table entries, so they need a smaller step size.
+const PHASE_LR_SCALE: f64 = 0.01;
+const BETA1: f64 = 0.9;
+const BETA2: f64 = 0.999;
+
+#[derive(Parser)]
+enum Cmd {
+    Texel(TexelArgs),
+}
+
+#[derive(Args, Debug)]
+struct TexelArgs {
+    /// Viriformat files written by `datagen`.
+    #[clap(required = true)]
+    inputs: Vec<PathBuf>,
+    /// File the tuned tables are written to. It's updated after every report.
+    #[clap(short, long, required = true)]
+    output: PathBuf,
+    /// Number of threads used to compute gradients. Defaults to `available_parallelism()`.
+    #[clap(short, long)]
+    threads: Option<usize>,
+    /// Number of full passes over the data.
+    #[clap(short, long, default_value_t = 2000)]
+    epochs: usize,
+    /// Adam learning rate, in centipawns.
+    #[clap(long, default_value_t = 1.0)]
+    lr: f64,
+    /// Maximum number of positions loaded.
+    #[clap(short, long)]
+    max_positions: Option<usize>,
+    /// Positions whose search score exceeds this are skipped.
+    #[clap(long, default_value_t = 3000)]
+    max_eval: i32,
+    /// Number of epochs between progress reports.
+    #[clap(long, default_value_t = 50)]
+    report_interval: usize,
+}
+
+/// Quiet positions, stored as lists of features.
+#[derive(Default)]
+struct Dataset {
+    /// Piece-square features of all positions, back to back. The low bits are the index into
+    /// the white relative piece-square table, and `BLACK` is set for black pieces.
+    features: Vec<u16>,
+    /// `ends[i]` is the end of the features of position `i`.
+    ends: Vec<usize>,
+    /// Game results from white's point of view.
+    results: Vec<f64>,
+    /// `pesto::eval` from white's point of view, used to fit the sigmoid scale.
+    evals: Vec<f64>,
+}
+
+const BLACK: u16 = 1 << 15;
+
+impl Dataset {
+    fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    fn features(&self, i: usize) -> &[u16] {
+        let start = if i == 0 { 0 } else { self.ends[i - 1] };
+        &self.features[start..self.ends[i]]
+    }
+
+    fn push(&mut self, board: &Board, result: f64) {
+        for color in [Color::White, Color::Black] {
+            for piece in Piece::all() {
+                for sq in board.colored_pieces(piece, color) {
+                    let (sq, flag) = match color {
+                        Color::White => (sq.idx(), 0),
+                        Color::Black => (sq.idx() ^ 56, BLACK),
+                    };
+                    self.features
+                        .push((piece.idx() as u16 * 64 + sq as u16) | flag);
+                }
+            }
+        }
+        self.ends.push(self.features.len());
+        self.results.push(result);
+
+        let eval = pesto::eval(board).0 as f64;
+        self.evals.push(if board.stm() == Color::White {
+            eval
+        } else {
+            -eval
+        });
+    }
+}
+
+pub fn texel() {
+    let Cmd::Texel(args) = Cmd::parse();
+
+    let start = Instant::now();
+    let mut data = Dataset::default();
+    for path in &args.inputs {
+        if let Err(e) = load(path, &args, &mut data) {
+            eprintln!("Failed to read `{}`: {e}", path.display());
+            return;
+        }
+    }
+    if data.len() == 0 {
+        eprintln!("No quiet positions found");
+        return;
+    }
+    println!(
+        "Loaded {} quiet positions in {:.1?}",
+        data.len(),
+        start.elapsed()
+    );
+
+    let threads = args
+        .threads
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, NonZero::get));
+
+    let k = fit_scale(&data);
+    let params = initial_params();
+    println!(
+        "Sigmoid scale {k:.6}, initial error {:.6}",
+        gradient(&data, &params, k, threads).0
+    );
+
+    fit(
+        &data,
+        params,
+        k,
+        args.epochs,
+        args.lr,
+        threads,
+        |epoch, error, params| {
+            if epoch % args.report_interval == 0 || epoch == args.epochs {
+                println!(
+                    "Epoch {epoch:>6}: error {error:.6}, elapsed {:.1?}",
+                    start.elapsed()
+                );
+                if let Err(e) = fs::write(&args.output, format_tables(params)) {
+                    eprintln!("Failed to write `{}`: {e}", args.output.display());
+                }
+            }
+        },
+    );
+}
+
+/// Fits `params` to `data` with Adam. `report` is called after every epoch with the error before
+/// the step and the updated parameters.
+fn fit(
+    data: &Dataset,
+    mut params: Vec<f64>,
+    k: f64,
+    epochs: usize,
+    lr: f64,
+    threads: usize,
+    mut report: impl FnMut(usize, f64, &[f64]),
+) -> Vec<f64> {
+    let mut m = vec![0.0; NUM_PARAMS];
+    let mut v = vec![0.0; NUM_PARAMS];
+    for epoch in 1..=epochs {
+        let (error, grad) = gradient(data, &params, k, threads);
+
+        let bias1 = 1.0 - BETA1.powi(epoch as i32);
+        let bias2 = 1.0 - BETA2.powi(epoch as i32);
+        for i in 0..NUM_PARAMS {
+            m[i] = BETA1 * m[i] + (1.0 - BETA1) * grad[i];
+            v[i] = BETA2 * v[i] + (1.0 - BETA2) * grad[i] * grad[i];
+            let lr = if i >= PHASE { lr * PHASE_LR_SCALE } else { lr };
+            params[i] -= lr * (m[i] / bias1) / ((v[i] / bias2).sqrt() + 1e-8);
+        }
+        // Phase weights can't be negative, or the phase could leave `0..=1`.
+        for w in &mut params[PHASE..] {
+            *w = w.max(0.0);
+        }
+
+        report(epoch, error, &params);
+    }
+
+    params
+}
+
+fn load(path: &Path, args: &TexelArgs, data: &mut Dataset) -> std::io::Result<()> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut buffer = Vec::new();
+
+    loop {
+        if args.max_positions.is_some_and(|max| data.len() >= max) {
+            return Ok(());
+        }
+
+        let game = match ViriGame::deserialise_from(&mut reader, buffer) {
+            Ok(game) => game,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        let result = match game.outcome() {
+            WDL::Win => 1.0,
+            WDL::Draw => 0.5,
+            WDL::Loss => 0.0,
+        };
+        let mut viri_board = game.initial_position();
+        buffer = game.into_move_buffer();
+
+        for &(mv, eval) in &buffer {
+            let quiet = !viri_board.in_check()
+                && !viri_board.is_tactical(mv)
+                && i32::from(eval.get()).abs() <= args.max_eval;
+            // Castling rights are written as `KQkq` even in DFRC, which can't always be parsed
+            // back, but they don't matter for the eval anyway.
+            let board = quiet
+                .then(|| {
+                    let fen = viri_board.to_string();
+                    let mut parts: Vec<&str> = fen.split(' ').collect();
+                    parts[2] = "-";
+                    Board::read_fen(&parts.join(" "))
+                })
+                .flatten();
+
+            if let Some(board) = board {
+                data.push(&board, result);
+            }
+            viri_board.make_move_simple(mv);
+        }
+    }
+}
+
+fn initial_params() -> Vec<f64> {
+    let mut params = vec![0.0; NUM_PARAMS];
+    for piece in 0..6 {
+        for sq in 0..64 {
+            params[piece * 64 + sq] = PESTO[0][piece][0][sq] as f64;
+            params[EG + piece * 64 + sq] = PESTO[1][piece][0][sq] as f64;
+        }
+        params[PHASE + piece] = PHASE_INC[piece] as f64;
+    }
+    params
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// Finds the sigmoid scale that minimizes the error of the current `pesto::eval`, using a
+/// ternary search.
+fn fit_scale(data: &Dataset) -> f64 {
+    let error = |k: f64| {
+        data.evals
+            .iter()
+            .zip(&data.results)
+            .map(|(&eval, &result)| (result - sigmoid(k * eval)).powi(2))
+            .sum::<f64>()
+    };
+
+    let (mut lo, mut hi) = (0.0, 0.1);
+    for _ in 0..100 {
+        let m1 = lo + (hi - lo) / 3.0;
+        let m2 = hi - (hi - lo) / 3.0;
+        if error(m1) < error(m2) {
+            hi = m2;
+        } else {
+            lo = m1;
+        }
+    }
+    (lo + hi) / 2.0
+}
+
+/// Returns the mean squared error and its gradient with respect to all parameters.
+fn gradient(data: &Dataset, params: &[f64], k: f64, threads: usize) -> (f64, Vec<f64>) {
+    let chunk = data.len().div_ceil(threads);
+
+    let (error, mut grad) = std::thread::scope(|s| {
+        let handles: Vec<_> = (0..data.len())
+            .step_by(chunk)
+            .map(|start| {
+                s.spawn(move || {
+                    partial_gradient(data, params, k, start..(start + chunk).min(data.len()))
+                })
+            })
+            .collect();
+
+        handles.into_iter().map(|h| h.join().unwrap()).fold(
+            (0.0, vec![0.0; NUM_PARAMS]),
+            |(error, mut grad), (e, g)| {
+                grad.iter_mut().zip(g).for_each(|(a, b)| *a += b);
+                (error + e, grad)
+            },
+        )
+    });
+
+    let n = data.len() as f64;
+    grad.iter_mut().for_each(|g| *g /= n);
+    // The king's phase weight is fixed, since there are always exactly two kings.
+    grad[PHASE + Piece::King.idx() as usize] = 0.0;
+    (error / n, grad)
+}
+
+fn partial_gradient(
+    data: &Dataset,
+    params: &[f64],
+    k: f64,
+    range: std::ops::Range<usize>,
+) -> (f64, Vec<f64>) {
+    let mut grad = vec![0.0; NUM_PARAMS];
+    let mut error = 0.0;
+
+    let weights = &params[PHASE..];
+    let max_phase: f64 = weights.iter().zip(START_COUNTS).map(|(w, c)| w * c).sum();
+
+    for i in range {
+        let features = data.features(i);
+
+        let (mut mg, mut eg) = (0.0, 0.0);
+        let mut counts = [0.0; 6];
+        for &f in features {
+            let sign = if f & BLACK != 0 { -1.0 } else { 1.0 };
+            let idx = (f & !BLACK) as usize;
+            mg += sign * params[idx];
+            eg += sign * params[EG + idx];
+            counts[idx / 64] += 1.0;
+        }
+
+        let phase: f64 = weights.iter().zip(counts).map(|(w, c)| w * c).sum();
+        let clamped = phase >= max_phase;
+        let p = if clamped { 1.0 } else { phase / max_phase };
+        let eval = eg + (mg - eg) * p;
+
+        let s = sigmoid(k * eval);
+        let diff = s - data.results[i];
+        error += diff * diff;
+
+        let d_eval = 2.0 * diff * s * (1.0 - s) * k;
+        for &f in features {
+            let sign = if f & BLACK != 0 { -1.0 } else { 1.0 };
+            let idx = (f & !BLACK) as usize;
+            grad[idx] += d_eval * sign * p;
+            grad[EG + idx] += d_eval * sign * (1.0 - p);
+        }
+        if !clamped {
+            for piece in 0..6 {
+                let d_p = (counts[piece] * max_phase - phase * START_COUNTS[piece])
+                    / (max_phase * max_phase);
+                grad[PHASE + piece] += d_eval * (mg - eg) * d_p;
+            }
+        }
+    }
+
+    (error, grad)
+}
+
+/// Formats the parameters like the tables in `pesto.rs`. The phase weights are rescaled so that
+/// the start position has a phase of 24 again.
+fn format_tables(params: &[f64]) -> String {
+    let weights = &params[PHASE..];
+    let max_phase: f64 = weights.iter().zip(START_COUNTS).map(|(w, c)| w * c).sum();
+    let phase_inc: Vec<String> = weights
+        .iter()
+        .map(|w| ((w * MAX_PHASE / max_phase).round() as u8).to_string())
+        .collect();
+
+    let mut out = format!(
+        "pub static PHASE_INC: [u8; 6] = [{}];\n\n",
+        phase_inc.join(", ")
+    );
+    out += "//indexed by [phase][piece][color][square]\n";
+    out += "#[rustfmt::skip]\n";
+    out += "pub static PESTO: [[[[i16; 64]; 2]; 6]; 2] = [\n";
+
+    for offset in [0, EG] {
+        out += "    [\n";
+        for piece in 0..6 {
+            out += "        [\n";
+            for color in [Color::White, Color::Black] {
+                out += "            [\n";
+                for rank in 0..8 {
+                    out += "                ";
+                    for file in 0..8 {
+                        let sq = rank * 8 + file;
+                        // Black's table is white's table mirrored and negated.
+                        let value = match color {
+                            Color::White => params[offset + piece * 64 + sq],
+                            Color::Black => -params[offset + piece * 64 + (sq ^ 56)],
+                        };
+                        let _ = write!(out, "{:>4}, ", value.round() as i16);
+                    }
+                    out += "\n";
+                }
+                out += "            ],\n";
+            }
+            out += "        ],\n";
+        }
+        out += "    ],\n";
+    }
+    out += "];\n";
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use arrayvec::ArrayVec;
+    use icarus_board::{board::Board, r#move::Move};
+    use icarus_common::piece::Piece;
+    use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+    use super::{
+        BLACK, Dataset, EG, MAX_PHASE, PHASE, PSQT_SIZE, START_COUNTS, fit, format_tables,
+        initial_params, sigmoid,
+    };
+    use crate::{bench::FENS, pesto::PHASE_INC, search::move_picker::MAX_MOVES};
+
+    #[test]
+    fn recovers_pesto() {
+        // Positions from random games starting at the bench positions, with the expected result
+        // of the current tables as their result.
+        let k = 0.005;
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut data = Dataset::default();
+        for fen in FENS {
+            let mut board = Board::read_fen(fen).unwrap();
+            for _ in 0..40 {
+                let moves: ArrayVec<Move, MAX_MOVES> = board.gen_all_moves_to();
+                if moves.is_empty() {
+                    break;
+                }
+                board.make_move(moves[rng.random_range(0..moves.len())]);
+                data.push(&board, 0.0);
+            }
+        }
+        data.results = data.evals.iter().map(|&eval| sigmoid(k * eval)).collect();
+
+        // Perturb the table entries that occur often enough to be fitted, and the knight weight.
+        // The king tables are left alone, since adding a constant to them doesn't change any eval.
+        let mut counts = [0; PSQT_SIZE];
+        for &f in &data.features {
+            counts[(f & !BLACK) as usize] += 1;
+        }
+        let perturbed: Vec<usize> = (0..Piece::King.idx() as usize * 64)
+            .filter(|&i| counts[i] >= data.len() / 10)
+            .collect();
+        let mut params = initial_params();
+        for &i in &perturbed {
+            params[i] += 30.0;
+            params[EG + i] -= 30.0;
+        }
+        params[PHASE + 1] = 2.0;
+
+        let params = fit(&data, params, k, 6000, 1.0, 1, |_, _, _| {});
+        let target = initial_params();
+        for &i in &perturbed {
+            for idx in [i, EG + i] {
+                let (value, target) = (params[idx], target[idx]);
+                assert!((value - target).abs() < 5.0, "{idx}: {value} vs {target}");
+            }
+        }
+        let max_phase: f64 = params[PHASE..]
+            .iter()
+            .zip(START_COUNTS)
+            .map(|(w, c)| w * c)
+            .sum();
+        for (piece, &inc) in PHASE_INC.iter().enumerate() {
+            let weight = params[PHASE + piece] * MAX_PHASE / max_phase;
+            assert!(
+                (weight - inc as f64).abs() < 0.25,
+                "{piece}: {weight} vs {inc}"
+            );
+        }
+    }
+
+    #[test]
+    fn tables_parse_back() {
+        // The current tables are printed exactly as they appear in `pesto.rs`.
+        let source = include_str!("../pesto.rs");
+        let text = format_tables(&initial_params());
+        let (phase_inc, tables) = text.split_once("\n\n").unwrap();
+        assert!(source.contains(phase_inc));
+        assert!(source.contains(tables));
+
+        let mut params = initial_params();
+        for (i, p) in params[..PHASE].iter_mut().enumerate() {
+            *p += (i % 7) as f64 * 1.3 - 4.0;
+        }
+        params[PHASE + 1] = 2.0;
+
+        let text = format_tables(&params);
+        let numbers = |s: &str| -> Vec<i32> {
+            s.split(|c: char| c != '-' && !c.is_ascii_digit())
+                .filter(|n| !n.is_empty())
+                .map(|n| n.parse().unwrap())
+                .collect()
+        };
+        let (phase_inc, tables) = text.split_once("\n\n").unwrap();
+        let phase_inc = numbers(phase_inc.split_once('=').unwrap().1);
+        let tables = numbers(tables.split_once('=').unwrap().1);
+
+        // The knight weight doubled, so everything else is rescaled by 24 / 28.
+        assert_eq!(phase_inc, [0, 2, 1, 2, 3, 0]);
+        assert_eq!(tables.len(), 2 * 6 * 2 * 64);
+        for (phase, offset) in [0, EG].into_iter().enumerate() {
+            for piece in 0..6 {
+                for sq in 0..64 {
+                    let table = |color: usize| (phase * 6 + piece) * 2 + color;
+                    let value = params[offset + piece * 64 + sq].round() as i32;
+                    assert_eq!(tables[table(0) * 64 + sq], value);
+                    assert_eq!(tables[table(1) * 64 + (sq ^ 56)], -value);
+                }
+            }
+        }
+    }
+}
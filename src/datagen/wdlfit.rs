@@ -0,0 +1,279 @@
+//! The `wdlfit` subcommand, which refits the WDL model from `wdl.rs` to viriformat games written
+//! by `datagen`. Every position contributes its search score and material count together with
+//! the game result, and the coefficients of the `a` and `b` polynomials are fitted by gradient
+//! descent on the negative log likelihood of the results.
+//!
+//! Positions are binned by material and score first, which keeps the fit fast no matter how many
+//! games there are. The fitted model is written in the format read by the `WdlModel` option.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufReader, ErrorKind},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use clap::{Args, Parser};
+use viriformat::dataformat::{Game as ViriGame, WDL};
+
+use crate::{score::Score, wdl::WdlModel};
+
+const BETA1: f64 = 0.9;
+const BETA2: f64 = 0.999;
+/// Lower bound for probabilities before taking their logarithm, since the draw probability of
+/// the model isn't guaranteed to be positive.
+const MIN_PROB: f64 = 1e-12;
+
+#[derive(Parser)]
+enum Cmd {
+    Wdlfit(WdlfitArgs),
+}
+
+#[derive(Args, Debug)]
+struct WdlfitArgs {
+    /// Viriformat files written by `datagen`.
+    #[clap(required = true)]
+    inputs: Vec<PathBuf>,
+    /// File the fitted model is written to.
+    #[clap(short, long, required = true)]
+    output: PathBuf,
+    /// Number of Adam steps. The learning rate decays linearly to zero over them.
+    #[clap(short, long, default_value_t = 3000)]
+    iterations: usize,
+    /// Initial Adam learning rate.
+    #[clap(long, default_value_t = 1.0)]
+    lr: f64,
+    /// Maximum number of positions loaded.
+    #[clap(short, long)]
+    max_positions: Option<usize>,
+    /// Positions whose search score exceeds this are skipped.
+    #[clap(long, default_value_t = 2000)]
+    max_eval: i16,
+}
+
+/// The positions with the same material input and score, and how often the game was won, drawn
+/// and lost from white's point of view.
+struct Bin {
+    m: f64,
+    score: f64,
+    counts: [f64; 3],
+}
+
+pub fn wdlfit() {
+    let Cmd::Wdlfit(args) = Cmd::parse();
+
+    let start = Instant::now();
+    let mut bins = HashMap::new();
+    let mut positions = 0;
+    for path in &args.inputs {
+        if let Err(e) = load(path, &args, &mut bins, &mut positions) {
+            eprintln!("Failed to read `{}`: {e}", path.display());
+            return;
+        }
+    }
+    if positions == 0 {
+        eprintln!("No positions found");
+        return;
+    }
+    let bins: Vec<Bin> = bins
+        .into_iter()
+        .map(|((mat, score), counts)| Bin {
+            m: WdlModel::material_input(mat),
+            score: score as f64,
+            counts,
+        })
+        .collect();
+    println!(
+        "Loaded {positions} positions into {} bins in {:.1?}",
+        bins.len(),
+        start.elapsed()
+    );
+
+    println!(
+        "Initial loss {:.6}",
+        gradient(&bins, &WdlModel::DEFAULT, positions as f64).0
+    );
+
+    let model = fit(&bins, positions as f64, args.iterations, args.lr);
+
+    println!(
+        "Final loss {:.6}, elapsed {:.1?}",
+        gradient(&bins, &model, positions as f64).0,
+        start.elapsed()
+    );
+    // `normalize` maps `a` to 100cp, so this is how far the eval scale drifted.
+    println!(
+        "At 58 material, 100cp is now {:.1} internal units, was {:.1}",
+        model.params(58).0,
+        WdlModel::DEFAULT.params(58).0
+    );
+
+    let text = format!("# wdlfit on {positions} positions\n{}", model.to_text());
+    match fs::write(&args.output, text) {
+        Ok(()) => println!("Wrote `{}`", args.output.display()),
+        Err(e) => eprintln!("Failed to write `{}`: {e}", args.output.display()),
+    }
+}
+
+/// Fits the model to `bins` with Adam, starting from `WdlModel::DEFAULT`.
+fn fit(bins: &[Bin], positions: f64, iterations: usize, lr: f64) -> WdlModel {
+    let mut model = WdlModel::DEFAULT;
+    let mut m = [0.0; 8];
+    let mut v = [0.0; 8];
+
+    for iter in 1..=iterations {
+        let (_, grad) = gradient(bins, &model, positions);
+
+        let lr = lr * (1.0 - (iter - 1) as f64 / iterations as f64);
+        let bias1 = 1.0 - BETA1.powi(iter as i32);
+        let bias2 = 1.0 - BETA2.powi(iter as i32);
+        let coeffs = model.a.iter_mut().chain(&mut model.b);
+        for (i, c) in coeffs.enumerate() {
+            m[i] = BETA1 * m[i] + (1.0 - BETA1) * grad[i];
+            v[i] = BETA2 * v[i] + (1.0 - BETA2) * grad[i] * grad[i];
+            *c -= lr * (m[i] / bias1) / ((v[i] / bias2).sqrt() + 1e-8);
+        }
+    }
+
+    model
+}
+
+fn load(
+    path: &Path,
+    args: &WdlfitArgs,
+    bins: &mut HashMap<(i16, i16), [f64; 3]>,
+    positions: &mut usize,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut buffer = Vec::new();
+
+    loop {
+        if args.max_positions.is_some_and(|max| *positions >= max) {
+            return Ok(());
+        }
+
+        let game = match ViriGame::deserialise_from(&mut reader, buffer) {
+            Ok(game) => game,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        let result = match game.outcome() {
+            WDL::Win => 0,
+            WDL::Draw => 1,
+            WDL::Loss => 2,
+        };
+        let mut viri_board = game.initial_position();
+        buffer = game.into_move_buffer();
+
+        for &(mv, eval) in &buffer {
+            let score = eval.get();
+            if !Score(score).is_mate() && score.abs() <= args.max_eval {
+                // Scores are stored from white's point of view, like the result.
+                let mat = classical_material(&viri_board.to_string());
+                let key = (mat.clamp(17, 78), score);
+                bins.entry(key).or_insert([0.0; 3])[result] += 1.0;
+                *positions += 1;
+            }
+            viri_board.make_move_simple(mv);
+        }
+    }
+}
+
+/// Same as `Board::classical_material`, but counted from the piece placement of `fen`.
+fn classical_material(fen: &str) -> i16 {
+    fen.split(' ')
+        .next()
+        .unwrap()
+        .chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            'p' => 1,
+            'n' | 'b' => 3,
+            'r' => 5,
+            'q' => 9,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Returns the mean negative log likelihood of the results and its gradient with respect to the
+/// coefficients of `a` followed by those of `b`.
+fn gradient(bins: &[Bin], model: &WdlModel, positions: f64) -> (f64, [f64; 8]) {
+    let mut loss = 0.0;
+    let mut grad = [0.0; 8];
+
+    for bin in bins {
+        let (a, b) = model.params_at(bin.m);
+        let powers = [bin.m * bin.m * bin.m, bin.m * bin.m, bin.m, 1.0];
+
+        // Win and loss are logistic in the score, the draw takes the rest.
+        let win_arg = (bin.score - a) / b;
+        let loss_arg = (-bin.score - a) / b;
+        let p_win = sigmoid(win_arg);
+        let p_loss = sigmoid(loss_arg);
+        let p_draw = 1.0 - p_win - p_loss;
+
+        let [wins, draws, losses] = bin.counts;
+        loss -= wins * p_win.max(MIN_PROB).ln()
+            + draws * p_draw.max(MIN_PROB).ln()
+            + losses * p_loss.max(MIN_PROB).ln();
+
+        let dwin = p_win * (1.0 - p_win) / b;
+        let dloss = p_loss * (1.0 - p_loss) / b;
+        let (dwin_da, dwin_db) = (-dwin, -dwin * win_arg);
+        let (dloss_da, dloss_db) = (-dloss, -dloss * loss_arg);
+
+        let dwin_weight = wins / p_win.max(MIN_PROB) - draws / p_draw.max(MIN_PROB);
+        let dloss_weight = losses / p_loss.max(MIN_PROB) - draws / p_draw.max(MIN_PROB);
+        let d_a = -(dwin_weight * dwin_da + dloss_weight * dloss_da);
+        let d_b = -(dwin_weight * dwin_db + dloss_weight * dloss_db);
+
+        for (i, p) in powers.iter().enumerate() {
+            grad[i] += d_a * p;
+            grad[4 + i] += d_b * p;
+        }
+    }
+
+    grad.iter_mut().for_each(|g| *g /= positions);
+    (loss / positions, grad)
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bin, fit, sigmoid};
+    use crate::wdl::WdlModel;
+
+    #[test]
+    fn recovers_model() {
+        // Expected result counts of a model with a larger eval scale than the default one.
+        let target = WdlModel {
+            a: WdlModel::DEFAULT.a.map(|c| c * 1.2),
+            b: WdlModel::DEFAULT.b.map(|c| c * 1.1),
+        };
+        let mut bins = vec![];
+        for mat in (17..=78).step_by(4) {
+            let m = WdlModel::material_input(mat);
+            let (a, b) = target.params_at(m);
+            for score in (-1500..=1500).step_by(25) {
+                let score = score as f64;
+                let (win, loss) = (sigmoid((score - a) / b), sigmoid((-score - a) / b));
+                let counts = [win, 1.0 - win - loss, loss].map(|p| p * 1000.0);
+                bins.push(Bin { m, score, counts });
+            }
+        }
+        let positions = bins.iter().map(|bin| bin.counts.iter().sum::<f64>()).sum();
+
+        let model = fit(&bins, positions, 3000, 1.0);
+        for mat in [20, 40, 58, 76] {
+            let (a, b) = model.params(mat);
+            let (target_a, target_b) = target.params(mat);
+            assert!((a - target_a).abs() < 5.0, "a at {mat}: {a} vs {target_a}");
+            assert!((b - target_b).abs() < 5.0, "b at {mat}: {b} vs {target_b}");
+        }
+    }
+}
@@ -1,32 +1,46 @@
 use std::{
+    path::Path,
     sync::LazyLock,
     time::{Duration, Instant},
 };
 
 use icarus_board::{board::Board, r#move::Move, movegen::Abort, perft::perft};
+use icarus_common::{
+    piece::{Color, Piece},
+    square::{File, Rank, Square},
+};
 use rustyline::{Config, Editor, error::ReadlineError, history::MemHistory};
 
 #[cfg(feature = "tune")]
-use crate::search::params::{list_params, print_params_ob, valid_param_name};
+use crate::search::params::{list_params, print_params_ob};
 use crate::{
-    bench::DEFAULT_BENCH_DEPTH,
+    bench,
     datagen::genfens,
-    nnue::network::Nnue,
+    nnue::{netinfo, network::Nnue, transform},
     position::Position,
+    score::Score,
     search::{
+        params::Params,
         searcher::{MAX_THREADS, Print, Searcher},
+        skill::{MAX_ELO, MAX_SKILL_LEVEL, MIN_ELO},
         time_manager::DEFAULT_MOVE_OVERHEAD,
         transposition_table::{DEFAULT_TT_SIZE, MAX_TT_SIZE},
     },
-    uci::{SearchLimit, UciCommand},
+    uci::{SearchLimit, UciCommand, UciParseError},
     util::atomic_instant::EPOCH,
-    wdl,
+    wdl::{self, WdlModel},
 };
 
+const MAX_CONTEMPT: i16 = 100;
+const MAX_NODES_TIME: u64 = 100000;
+
 pub struct Engine {
     position: Position,
+    /// FEN of the board `position` started from, used to detect `position` commands that only
+    /// extend the current game.
+    start_fen: String,
     use_soft_nodes: bool,
-    chess960: bool,
+    pub(crate) chess960: bool,
     move_overhead: u64,
     minimal: bool,
     searcher: Searcher,
@@ -36,6 +50,7 @@ impl Engine {
     pub fn new() -> Self {
         Self {
             position: Position::new(Board::start_pos()),
+            start_fen: Board::start_pos().fen(true),
             use_soft_nodes: false,
             chess960: false,
             move_overhead: DEFAULT_MOVE_OVERHEAD,
@@ -50,8 +65,18 @@ impl Engine {
 
         let argv: Vec<String> = std::env::args().skip(1).collect();
 
-        if argv == ["bench"] {
-            self.bench(DEFAULT_BENCH_DEPTH, true);
+        // `icarus bench ...`, `icarus nnuebench ...`, `icarus netinfo ...` and
+        // `icarus nettransform ...` take the same arguments as the commands of the same name, and
+        // exit after.
+        if argv.first().is_some_and(|s| {
+            ["bench", "nnuebench", "netinfo", "nettransform"].contains(&s.as_str())
+        }) {
+            match UciCommand::parse(&argv.join(" "), self.position.board(), self.chess960) {
+                Ok(command) => {
+                    self.handle_cmd(command);
+                }
+                Err(e) => eprintln!("info string {e}"),
+            }
             return Ok(());
         }
 
@@ -61,6 +86,36 @@ impl Engine {
             return Ok(());
         }
 
+        #[cfg(feature = "datagen")]
+        if argv.first().is_some_and(|s| s == "texel") {
+            crate::datagen::texel::texel();
+            return Ok(());
+        }
+
+        #[cfg(feature = "datagen")]
+        if argv.first().is_some_and(|s| s == "wdlfit") {
+            crate::datagen::wdlfit::wdlfit();
+            return Ok(());
+        }
+
+        #[cfg(feature = "selfplay")]
+        if argv.first().is_some_and(|s| s == "match") {
+            crate::selfplay::matches::run_match();
+            return Ok(());
+        }
+
+        #[cfg(feature = "selfplay")]
+        if argv.first().is_some_and(|s| s == "calibrate") {
+            crate::selfplay::calibrate::calibrate();
+            return Ok(());
+        }
+
+        #[cfg(feature = "selfplay")]
+        if argv.first().is_some_and(|s| s == "spsa") {
+            crate::selfplay::spsa::run_spsa();
+            return Ok(());
+        }
+
         #[cfg(feature = "test-islegal")]
         if argv == ["test_islegal"] {
             icarus_board::is_legal::test::test_islegal(&crate::bench::FENS);
@@ -110,6 +165,7 @@ impl Engine {
             UciCommand::Uci => self.uci(),
             UciCommand::NewGame => self.searcher.newgame(),
             UciCommand::IsReady => self.isready(),
+            UciCommand::Debug(debug) => self.searcher.set_debug(debug),
             UciCommand::SetOption { name, value } => self.setoption(name, value),
             UciCommand::Position {
                 board,
@@ -119,7 +175,26 @@ impl Engine {
             UciCommand::Go(search_limits) => self.go(search_limits),
             UciCommand::Eval => self.eval(),
             UciCommand::Display => self.display(),
-            UciCommand::Bench { depth, .. } => self.bench(depth, false),
+            UciCommand::Bench(config) => self.bench(&config),
+            UciCommand::NnueBench(depth) => bench::nnue_bench(depth),
+            UciCommand::NetInfo { net, positions } => {
+                netinfo::netinfo(net.as_deref(), positions.as_deref())
+            }
+            UciCommand::NetTransform {
+                net,
+                positions,
+                out,
+                prune,
+                permute,
+                compress,
+            } => transform::nettransform(
+                net.as_deref(),
+                positions.as_deref(),
+                &out,
+                prune,
+                permute,
+                compress,
+            ),
             UciCommand::Perft { depth, bulk } => self.perft(depth, bulk),
             UciCommand::SplitPerft { depth, bulk } => self.splitperft(depth, bulk),
             UciCommand::GenFens {
@@ -134,8 +209,9 @@ impl Engine {
                 return Abort::Yes;
             }
             UciCommand::Wait => self.wait(true),
+            UciCommand::Stats => self.stats(),
             #[cfg(feature = "tune")]
-            UciCommand::Params => print_params_ob(),
+            UciCommand::Params => print_params_ob(self.searcher.params()),
         }
 
         Abort::No
@@ -155,8 +231,29 @@ impl Engine {
         println!("option name Hash type spin default {DEFAULT_TT_SIZE} min 1 max {MAX_TT_SIZE}");
         println!("option name Threads type spin default 1 min 1 max {MAX_THREADS}");
         println!("option name Minimal type check default false");
+        println!("option name Deterministic type check default false");
+        println!("option name NodesTime type spin default 0 min 0 max {MAX_NODES_TIME}");
+        println!("option name Contempt type spin default 0 min -{MAX_CONTEMPT} max {MAX_CONTEMPT}");
+        println!("option name WdlContempt type spin default 0 min 0 max {MAX_CONTEMPT}");
+        println!(
+            "option name Skill Level type spin default {MAX_SKILL_LEVEL} min 0 max {MAX_SKILL_LEVEL}"
+        );
+        println!("option name UCI_LimitStrength type check default false");
+        println!("option name UCI_Elo type spin default {MIN_ELO} min {MIN_ELO} max {MAX_ELO}");
+        #[cfg(feature = "trace")]
+        {
+            use crate::{search::trace::DEFAULT_TRACE_PLY, util::MAX_PLY};
+
+            println!("option name TraceFile type string default <empty>");
+            println!(
+                "option name TracePly type spin default {DEFAULT_TRACE_PLY} min 0 max {MAX_PLY}"
+            );
+        }
+        println!("option name WdlModel type string default <empty>");
+        println!("option name LoadParams type string default <empty>");
+        println!("option name SaveParams type string default <empty>");
         #[cfg(feature = "tune")]
-        list_params();
+        list_params(self.searcher.params());
         println!("uciok");
     }
 
@@ -232,31 +329,221 @@ impl Engine {
                 self.minimal = val;
                 println!("info string Set Minimal to {val}");
             }
-            #[cfg(feature = "tune")]
-            name if valid_param_name(name) => {
-                use crate::search::params::set_param;
+            "nodestime" => {
+                if self.searcher.is_running() {
+                    println!("info string Can't update NodesTime while searching");
+                    return;
+                }
 
+                let Ok(val) = value.parse::<u64>() else {
+                    println!("info string Unknown value {value}");
+                    return;
+                };
+                if val > MAX_NODES_TIME {
+                    println!("info string Invalid NodesTime value!");
+                    return;
+                }
+                self.searcher.set_nodes_time(val);
+                println!("info string Set NodesTime to {val}");
+            }
+            "deterministic" => {
+                if self.searcher.is_running() {
+                    println!("info string Can't update Deterministic while searching");
+                    return;
+                }
+
+                let Ok(val) = value.parse::<bool>() else {
+                    println!("info string Unknown value {value}");
+                    return;
+                };
+                self.searcher.set_deterministic(val);
+                println!("info string Set Deterministic to {val}");
+            }
+            "contempt" => {
+                let Ok(val) = value.parse::<i16>() else {
+                    println!("info string Unknown value {value}");
+                    return;
+                };
+                if !(-MAX_CONTEMPT..=MAX_CONTEMPT).contains(&val) {
+                    println!("info string Invalid Contempt value!");
+                    return;
+                }
+                self.searcher.set_contempt(Score(val));
+                println!("info string Set Contempt to {val}");
+            }
+            "wdlcontempt" => {
+                let Ok(val) = value.parse::<i16>() else {
+                    println!("info string Unknown value {value}");
+                    return;
+                };
+                if !(0..=MAX_CONTEMPT).contains(&val) {
+                    println!("info string Invalid WdlContempt value!");
+                    return;
+                }
+                self.searcher.set_wdl_contempt(Score(val));
+                println!("info string Set WdlContempt to {val}");
+            }
+            "skill level" | "skilllevel" => {
+                let Ok(val) = value.parse::<u8>() else {
+                    println!("info string Unknown value {value}");
+                    return;
+                };
+                if val > MAX_SKILL_LEVEL {
+                    println!("info string Invalid Skill Level value!");
+                    return;
+                }
+                let mut skill = *self.searcher.skill();
+                skill.level = val;
+                self.searcher.set_skill(skill);
+                println!("info string Set Skill Level to {val}");
+            }
+            "uci_limitstrength" => {
+                let Ok(val) = value.parse::<bool>() else {
+                    println!("info string Unknown value {value}");
+                    return;
+                };
+                let mut skill = *self.searcher.skill();
+                skill.limit_strength = val;
+                self.searcher.set_skill(skill);
+                println!("info string Set UCI_LimitStrength to {val}");
+            }
+            "uci_elo" => {
+                let Ok(val) = value.parse::<u16>() else {
+                    println!("info string Unknown value {value}");
+                    return;
+                };
+                if !(MIN_ELO..=MAX_ELO).contains(&val) {
+                    println!("info string Invalid UCI_Elo value!");
+                    return;
+                }
+                let mut skill = *self.searcher.skill();
+                skill.elo = val;
+                self.searcher.set_skill(skill);
+                println!("info string Set UCI_Elo to {val}");
+            }
+            #[cfg(feature = "trace")]
+            "tracefile" => {
+                let mut trace = self.searcher.trace().clone();
+                trace.path = (value != "<empty>").then(|| value.into());
+                match &trace.path {
+                    Some(path) => println!("info string Tracing searches to {}", path.display()),
+                    None => println!("info string Disabled search tracing"),
+                }
+                self.searcher.set_trace(trace);
+            }
+            #[cfg(feature = "trace")]
+            "traceply" => {
+                let Ok(val) = value.parse::<u16>() else {
+                    println!("info string Unknown value {value}");
+                    return;
+                };
+                let mut trace = self.searcher.trace().clone();
+                trace.max_ply = val;
+                self.searcher.set_trace(trace);
+                println!("info string Set TracePly to {val}");
+            }
+            "wdlmodel" => {
+                if value == "<empty>" {
+                    return;
+                }
+                if self.searcher.is_running() {
+                    println!("info string Can't load WDL model while searching");
+                    return;
+                }
+
+                match WdlModel::load(Path::new(&value)) {
+                    Ok(model) => {
+                        wdl::set_model(model);
+                        println!("info string Loaded WDL model from {value}");
+                    }
+                    Err(e) => println!("info string {e}"),
+                }
+            }
+            "loadparams" => {
+                if value == "<empty>" {
+                    return;
+                }
+                if self.searcher.is_running() {
+                    println!("info string Can't load parameters while searching");
+                    return;
+                }
+
+                match Params::load(Path::new(&value)) {
+                    Ok(params) => {
+                        self.searcher.set_params(params);
+                        println!("info string Loaded parameters from {value}");
+                    }
+                    Err(e) => println!("info string {e}"),
+                }
+            }
+            "saveparams" => {
+                if value == "<empty>" {
+                    return;
+                }
+
+                match self.searcher.params().save(Path::new(&value)) {
+                    Ok(()) => println!("info string Saved parameters to {value}"),
+                    Err(e) => println!("info string {e}"),
+                }
+            }
+            #[cfg(feature = "tune")]
+            name if self.searcher.params().get(name).is_some() => {
                 if self.searcher.is_running() {
                     println!("info string Can't update tunable while searching");
                     return;
                 }
 
-                set_param(name, &value);
+                let mut params = *self.searcher.params();
+                if value.parse().is_ok_and(|val| params.set(name, val)) {
+                    self.searcher.set_params(params);
+                } else {
+                    println!("info string Invalid value {value} for {name}");
+                }
             }
             _ => println!("info string Unsupported option {name}"),
         }
     }
 
-    fn position(&mut self, board: Board, moves: Vec<Move>, enable_960: bool) {
+    fn position(&mut self, board: Board, moves: Vec<String>, enable_960: bool) {
         if enable_960 && !self.chess960 {
             println!("info string Enabling Chess960");
             self.chess960 = true;
         }
 
-        self.position = Position::new(board);
-        for mv in moves {
-            self.position.make_move(mv, None);
+        // If the new position starts from the same board as the current one, we only take back
+        // the moves that aren't shared with the new move list, and then play the remaining ones.
+        let start_fen = board.fen(true);
+        let (mut position, common) = if start_fen == self.start_fen {
+            let played: Vec<Move> = self.position.moves().collect();
+            let common = played
+                .iter()
+                .zip(&moves)
+                .take_while(|(mv, s)| mv.display(self.chess960) == **s)
+                .count();
+
+            let mut position = self.position.clone();
+            for _ in common..played.len() {
+                position.unmake_move(None);
+            }
+            (position, common)
+        } else {
+            (Position::new(board), 0)
+        };
+
+        for s in &moves[common..] {
+            let board = position.board();
+            let Some(mv) = board
+                .parse_move(s, self.chess960)
+                .filter(|&mv| board.is_legal_thorough(mv))
+            else {
+                eprintln!("info string {}", UciParseError::InvalidMove(s.clone()));
+                return;
+            };
+            position.make_move(mv, None);
         }
+
+        self.position = position;
+        self.start_fen = start_fen;
     }
 
     fn display(&self) {
@@ -362,13 +649,77 @@ impl Engine {
         }
     }
 
-    fn eval(&self) {
-        let mut nnue = Nnue::new(self.position.board());
-        let score = self.position.eval(&mut nnue, false);
-        let scaled_score = self.position.eval(&mut nnue, true);
+    fn stats(&self) {
+        if self.searcher.is_running() {
+            println!("info string Can't print statistics while searching");
+            return;
+        }
+
+        self.searcher.global_ctx.stats.lock().unwrap().print();
+    }
 
-        let material = self.position.board().classical_material();
+    fn eval(&mut self) {
+        let board = self.position.board();
+        let mut nnue = Nnue::new(board);
+        let params = self.searcher.params();
+        let score = self.position.eval(&mut nnue, false, params);
+        let scaled_score = self.position.eval(&mut nnue, true, params);
+
+        // How much each piece contributes to the eval, from white's perspective. This is the
+        // difference to the eval without that piece, so the contributions don't sum up to the
+        // total eval. Kings can't be removed.
+        let white_pov = |eval: i32| board.stm().signum() as i32 * eval;
+        let full = nnue.eval(board.stm());
+        let line = |left: char, fill: char, mid: char, right: char| {
+            let cell = fill.to_string().repeat(7);
+            format!("{left}{}{cell}{right}", format!("{cell}{mid}").repeat(7))
+        };
+        println!("{}", line('╔', '═', '╤', '╗'));
+        for &rank in Rank::ALL.iter().rev() {
+            let (mut pieces, mut values) = (String::from("║"), String::from("║"));
+            for &file in File::ALL {
+                let sq = Square::new(file, rank);
+                let border = if file == File::H { '║' } else { '│' };
+                let Some(piece) = board.piece_on(sq) else {
+                    pieces += &format!("       {border}");
+                    values += &format!("       {border}");
+                    continue;
+                };
+
+                let color = if board.occupied_by(Color::White).contains(sq) {
+                    Color::White
+                } else {
+                    Color::Black
+                };
+                pieces += &format!("   {}   {border}", piece.to_char(color));
+                values += &if piece == Piece::King {
+                    format!("       {border}")
+                } else {
+                    let delta = white_pov(full - nnue.eval_without(board, sq));
+                    format!(" {delta:>+5} {border}")
+                };
+            }
+            println!("{pieces}\n{values}");
+            if rank != Rank::R1 {
+                println!("{}", line('╟', '─', '┼', '╢'));
+            }
+        }
+        println!("{}", line('╚', '═', '╧', '╝'));
+
+        let material = board.classical_material();
         let normalized = wdl::normalize(scaled_score, material);
+        // The network has no output buckets, so every position uses the same output layer.
+        println!("Output bucket:          0 of 1");
+        println!(
+            "Material scaling:       {:.3}",
+            self.position.material_scale(params) as f64 / 32768.0
+        );
+        if self.searcher.is_running() {
+            println!("Correction history:     unavailable while searching");
+        } else {
+            let corr = self.searcher.corr(&self.position);
+            println!("Correction history:     {corr:+}");
+        }
         println!("Raw eval:               {score:#}");
         println!("Normalized scaled eval: {normalized:#}")
     }
@@ -379,3 +730,94 @@ impl Default for Engine {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use icarus_board::board::Board;
+
+    use crate::{engine::Engine, position::Position};
+
+    const FEN: &str = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+
+    fn board(fen: Option<&str>) -> Board {
+        fen.map_or(Board::start_pos(), |fen| Board::read_fen(fen).unwrap())
+    }
+
+    fn moves(moves: &str) -> Vec<String> {
+        moves.split_whitespace().map(String::from).collect()
+    }
+
+    /// Sends a `position` command, and checks that the result is the same as when building the
+    /// position from scratch.
+    fn check(engine: &mut Engine, fen: Option<&str>, line: &str) {
+        let board = board(fen);
+        engine.position(board, moves(line), false);
+
+        let mut fresh = Position::new(board);
+        for s in moves(line) {
+            fresh.make_move(fresh.board().parse_move(&s, false).unwrap(), None);
+        }
+
+        let mut position = engine.position.clone();
+        assert_eq!(engine.start_fen, board.fen(true));
+        assert_eq!(
+            position.moves().collect::<Vec<_>>(),
+            fresh.moves().collect::<Vec<_>>()
+        );
+        assert_eq!(position.board().fen(true), fresh.board().fen(true));
+        assert_eq!(position.repetition(), fresh.repetition());
+
+        // Taking back every move has to arrive at the starting board again.
+        for _ in position.moves().collect::<Vec<_>>() {
+            position.unmake_move(None);
+        }
+        assert_eq!(position.board().fen(true), board.fen(true));
+        assert_eq!(position.board().hash(), board.hash());
+    }
+
+    /// Sends a `position` command with an illegal move, which has to leave the position as it was.
+    fn check_rejected(engine: &mut Engine, fen: Option<&str>, line: &str) {
+        let (start_fen, fen_before) = (engine.start_fen.clone(), engine.position.board().fen(true));
+        let moves_before: Vec<_> = engine.position.moves().collect();
+
+        engine.position(board(fen), moves(line), false);
+
+        assert_eq!(engine.start_fen, start_fen);
+        assert_eq!(engine.position.moves().collect::<Vec<_>>(), moves_before);
+        assert_eq!(engine.position.board().fen(true), fen_before);
+    }
+
+    #[test]
+    fn position_reuses_common_moves() {
+        let mut engine = Engine::new();
+        check(&mut engine, None, "e2e4 e7e5");
+        // Extending the current line.
+        check(&mut engine, None, "e2e4 e7e5 g1f3 b8c6 f1b5");
+        // Branching off partway.
+        check(&mut engine, None, "e2e4 e7e5 b1c3");
+        check(&mut engine, None, "d2d4");
+        check(&mut engine, None, "");
+        // Repeating positions across the common prefix.
+        check(&mut engine, None, "g1f3 g8f6 f3g1");
+        check(&mut engine, None, "g1f3 g8f6 f3g1 f6g8 g1f3");
+    }
+
+    #[test]
+    fn position_changes_start() {
+        let mut engine = Engine::new();
+        check(&mut engine, None, "e2e4 e7e5 g1f3 b8c6");
+        check(&mut engine, Some(FEN), "f1b5");
+        check(&mut engine, Some(FEN), "f1b5 a7a6");
+        check(&mut engine, None, "e2e4 e7e5 g1f3 b8c6 f1b5");
+    }
+
+    #[test]
+    fn position_keeps_old_position_on_illegal_move() {
+        let mut engine = Engine::new();
+        check(&mut engine, None, "e2e4 e7e5 g1f3");
+        check_rejected(&mut engine, None, "e2e4 e7e5 g1f3 e1e3");
+        check_rejected(&mut engine, None, "e2e4 c7c5 a1a5");
+        check_rejected(&mut engine, Some(FEN), "e1g1");
+        check(&mut engine, None, "e2e4 e7e5 g1f3 b8c6");
+    }
+}
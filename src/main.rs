@@ -4,9 +4,13 @@ pub mod bench;
 pub mod datagen;
 pub mod engine;
 pub mod nnue;
+#[cfg(feature = "datagen")]
+pub mod pesto;
 pub mod position;
 pub mod score;
 pub mod search;
+#[cfg(feature = "selfplay")]
+pub mod selfplay;
 pub mod uci;
 pub mod util;
 pub mod wdl;
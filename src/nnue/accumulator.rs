@@ -1,19 +1,38 @@
 use arrayvec::ArrayVec;
+use icarus_board::setwise_attacks::knight_and_slider_attacks_setwise;
 use icarus_common::{
     bitboard::Bitboard,
+    direction::{DownLeft, DownRight},
+    lookups::king_moves,
     piece::{Color, Piece},
     square::Square,
-    util::enum_map::EnumMap,
+    util::enum_map::{EnumMap, enum_map},
 };
 
-use crate::nnue::network::{HL, INPUT, NET, NUM_KING_BUCKETS, should_mirror};
+use crate::nnue::network::{HL_ALIGN, Network};
 
 #[derive(Debug, Clone)]
 pub struct Accumulator {
-    pub values: EnumMap<Color, [i16; HL]>,
+    pub values: EnumMap<Color, Box<[i16]>>,
     pub dirty: EnumMap<Color, bool>,
     pub needs_refresh: EnumMap<Color, bool>,
     pub updates: Updates,
+    /// The pieces of the position this accumulator belongs to that are attacked by the enemy.
+    /// Only kept up to date if the net has threat inputs.
+    pub threats: Threats,
+}
+
+impl Accumulator {
+    /// An accumulator for a hidden layer of `hl` neurons, which has to be reset before use.
+    pub fn new(hl: usize) -> Self {
+        Self {
+            values: enum_map! { _ => vec![0; hl].into_boxed_slice() },
+            dirty: Default::default(),
+            needs_refresh: Default::default(),
+            updates: Default::default(),
+            threats: Default::default(),
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -24,13 +43,15 @@ pub struct Feature {
 }
 
 impl Feature {
-    pub fn idx(&self, perspective: Color, king: Square) -> usize {
+    /// The index of this feature from the view of `perspective`, where `mirror` is whether
+    /// `perspective`'s king is on the mirrored half of the board.
+    pub fn idx(&self, perspective: Color, mirror: bool) -> usize {
         let (mut square, mut color) = match perspective {
             Color::White => (self.square, self.color),
             Color::Black => (self.square.flip_rank(), !self.color),
         };
 
-        if should_mirror(king) {
+        if mirror {
             square = square.flip_file();
         }
         if self.piece == Piece::King {
@@ -41,6 +62,78 @@ impl Feature {
     }
 }
 
+/// Pieces that are attacked by an enemy piece, indexed by `[color][piece]`. Each of them activates
+/// a threat feature, which has the same index as the regular feature of the piece, but uses the
+/// weights in `Network::threat_weight`.
+pub type Threats = EnumMap<Color, EnumMap<Piece, Bitboard>>;
+
+/// Calculates the threat features of a position given by its piece and color bitboards. This
+/// doesn't take a `Board`, so that it also works for positions that are only the result of applying
+/// `Updates`.
+pub fn threats(pieces: &EnumMap<Piece, Bitboard>, colors: &EnumMap<Color, Bitboard>) -> Threats {
+    let occupied = colors[Color::White] | colors[Color::Black];
+    let attacks = enum_map! { color => {
+        let ours = |piece: Piece| pieces[piece] & colors[color];
+        let pawns = ours(Piece::Pawn);
+        let orth = ours(Piece::Rook) | ours(Piece::Queen);
+        let diag = ours(Piece::Bishop) | ours(Piece::Queen);
+        let push_dir = (!color).signum();
+
+        pawns.shift::<DownLeft>(push_dir)
+            | pawns.shift::<DownRight>(push_dir)
+            | ours(Piece::King).into_iter().fold(Bitboard::EMPTY, |bb, k| bb | king_moves(k))
+            | knight_and_slider_attacks_setwise(ours(Piece::Knight), orth, diag, occupied)
+    }};
+
+    enum_map! { color => enum_map! { piece => pieces[piece] & colors[color] & attacks[!color] } }
+}
+
+/// All threat features in `threats`.
+pub fn threat_features(threats: &Threats) -> impl Iterator<Item = Feature> + '_ {
+    Color::all().flat_map(move |color| {
+        Piece::all().flat_map(move |piece| {
+            threats[color][piece]
+                .into_iter()
+                .map(move |square| Feature {
+                    piece,
+                    color,
+                    square,
+                })
+        })
+    })
+}
+
+/// Applies the difference between the threats of two positions to `acc`.
+pub fn acc_threat_diff(
+    acc: &mut [i16],
+    weights: &[i16],
+    (old, new): (&Threats, &Threats),
+    perspective: Color,
+    mirror: bool,
+) {
+    for color in Color::all() {
+        for piece in Piece::all() {
+            let (old, new) = (old[color][piece], new[color][piece]);
+            for square in new & !old {
+                let feature = Feature {
+                    piece,
+                    color,
+                    square,
+                };
+                acc_add(acc, weights, feature.idx(perspective, mirror));
+            }
+            for square in old & !new {
+                let feature = Feature {
+                    piece,
+                    color,
+                    square,
+                };
+                acc_sub(acc, weights, feature.idx(perspective, mirror));
+            }
+        }
+    }
+}
+
 #[derive(Clone, Default, Debug)]
 pub struct Updates {
     pub adds: ArrayVec<Feature, 2>,
@@ -70,125 +163,205 @@ impl Updates {
     }
 }
 
-#[repr(align(64))]
-#[derive(Default)]
 /// An accumulator cache for refreshing on king bucket changes, also known as "Finny tables".
 pub struct KingBucketCache {
     /// indexed by [stm][mirror][bucket]
-    pub entries: [[[Entry; NUM_KING_BUCKETS]; 2]; 2],
+    entries: Vec<Entry>,
+    num_buckets: usize,
 }
 
 pub struct Entry {
-    pub features: [i16; HL],
+    pub features: Box<[i16]>,
     pub pieces: EnumMap<Piece, Bitboard>,
     pub colors: EnumMap<Color, Bitboard>,
 }
 
-impl Default for Entry {
-    fn default() -> Self {
+impl KingBucketCache {
+    /// A cache with an entry for every king bucket of `net`, each of them an empty board.
+    pub fn new(net: &Network) -> Self {
+        let num_buckets = net.header.num_king_buckets as usize;
+        let entries = (0..4 * num_buckets)
+            .map(|_| Entry {
+                features: Box::from(&net.ft_bias[..]),
+                pieces: Default::default(),
+                colors: Default::default(),
+            })
+            .collect();
         Self {
-            features: NET.ft_bias,
-            pieces: Default::default(),
-            colors: Default::default(),
+            entries,
+            num_buckets,
         }
     }
+
+    pub fn entry(&mut self, perspective: Color, mirror: bool, bucket: usize) -> &mut Entry {
+        let idx = (perspective as usize * 2 + mirror as usize) * self.num_buckets + bucket;
+        &mut self.entries[idx]
+    }
+
+    /// Resets every entry to an empty board, using the feature biases of `net`.
+    pub fn clear(&mut self, net: &Network) {
+        for entry in &mut self.entries {
+            entry.features.copy_from_slice(&net.ft_bias);
+            entry.pieces = Default::default();
+            entry.colors = Default::default();
+        }
+    }
+}
+
+/// Row `feature` of `weights`, whose rows are `hl` neurons long.
+fn row(weights: &[i16], feature: usize, hl: usize) -> &[i16] {
+    &weights[feature * hl..][..hl]
+}
+
+/// Splits `values` into tiles of `HL_ALIGN` neurons. The loops over a tile have a fixed length,
+/// so they are vectorized just as well as if the hidden layer size was a constant.
+fn tiles(values: &[i16]) -> &[[i16; HL_ALIGN]] {
+    values.as_chunks().0
 }
 
-pub fn acc_add(acc: &mut [i16; HL], weights: &[[i16; HL]; INPUT], add: usize) {
-    for (acc, weight) in acc.iter_mut().zip(&weights[add]) {
-        *acc += *weight;
+fn tiles_mut(values: &mut [i16]) -> &mut [[i16; HL_ALIGN]] {
+    values.as_chunks_mut().0
+}
+
+pub fn acc_add(acc: &mut [i16], weights: &[i16], add: usize) {
+    let add = tiles(row(weights, add, acc.len()));
+
+    for (acc, add) in tiles_mut(acc).iter_mut().zip(add) {
+        for i in 0..HL_ALIGN {
+            acc[i] += add[i];
+        }
     }
 }
 
-pub fn acc_sub(acc: &mut [i16; HL], weights: &[[i16; HL]; INPUT], sub: usize) {
-    for (acc, weight) in acc.iter_mut().zip(&weights[sub]) {
-        *acc -= *weight;
+pub fn acc_sub(acc: &mut [i16], weights: &[i16], sub: usize) {
+    let sub = tiles(row(weights, sub, acc.len()));
+
+    for (acc, sub) in tiles_mut(acc).iter_mut().zip(sub) {
+        for i in 0..HL_ALIGN {
+            acc[i] -= sub[i];
+        }
     }
 }
 
-pub fn acc_add_sub(
-    src: &[i16; HL],
-    dst: &mut [i16; HL],
-    weights: &[[i16; HL]; INPUT],
-    add: usize,
-    sub: usize,
-) {
-    let add: &[i16; HL] = &weights[add];
-    let sub: &[i16; HL] = &weights[sub];
+pub fn acc_add_sub(src: &[i16], dst: &mut [i16], weights: &[i16], add: usize, sub: usize) {
+    let hl = src.len();
+    let add = tiles(row(weights, add, hl));
+    let sub = tiles(row(weights, sub, hl));
 
-    for i in 0..HL {
-        dst[i] = src[i] + add[i] - sub[i];
+    for (t, (dst, src)) in tiles_mut(dst).iter_mut().zip(tiles(src)).enumerate() {
+        for i in 0..HL_ALIGN {
+            dst[i] = src[i] + add[t][i] - sub[t][i];
+        }
     }
 }
 
 pub fn acc_add_sub2(
-    src: &[i16; HL],
-    dst: &mut [i16; HL],
-    weights: &[[i16; HL]; INPUT],
+    src: &[i16],
+    dst: &mut [i16],
+    weights: &[i16],
     add: usize,
     sub1: usize,
     sub2: usize,
 ) {
-    let add: &[i16; HL] = &weights[add];
-    let sub1: &[i16; HL] = &weights[sub1];
-    let sub2: &[i16; HL] = &weights[sub2];
+    let hl = src.len();
+    let add = tiles(row(weights, add, hl));
+    let sub1 = tiles(row(weights, sub1, hl));
+    let sub2 = tiles(row(weights, sub2, hl));
 
-    for i in 0..HL {
-        dst[i] = src[i] + add[i] - sub1[i] - sub2[i];
+    for (t, (dst, src)) in tiles_mut(dst).iter_mut().zip(tiles(src)).enumerate() {
+        for i in 0..HL_ALIGN {
+            dst[i] = src[i] + add[t][i] - sub1[t][i] - sub2[t][i];
+        }
     }
 }
 
 pub fn acc_add2_sub2(
-    src: &[i16; HL],
-    dst: &mut [i16; HL],
-    weights: &[[i16; HL]; INPUT],
+    src: &[i16],
+    dst: &mut [i16],
+    weights: &[i16],
     add1: usize,
     add2: usize,
     sub1: usize,
     sub2: usize,
 ) {
-    let add1: &[i16; HL] = &weights[add1];
-    let add2: &[i16; HL] = &weights[add2];
-    let sub1: &[i16; HL] = &weights[sub1];
-    let sub2: &[i16; HL] = &weights[sub2];
+    let hl = src.len();
+    let add1 = tiles(row(weights, add1, hl));
+    let add2 = tiles(row(weights, add2, hl));
+    let sub1 = tiles(row(weights, sub1, hl));
+    let sub2 = tiles(row(weights, sub2, hl));
 
-    for i in 0..HL {
-        dst[i] = src[i] + add1[i] + add2[i] - sub1[i] - sub2[i];
+    for (t, (dst, src)) in tiles_mut(dst).iter_mut().zip(tiles(src)).enumerate() {
+        for i in 0..HL_ALIGN {
+            dst[i] = src[i] + add1[t][i] + add2[t][i] - sub1[t][i] - sub2[t][i];
+        }
     }
 }
 
 pub fn acc_add4(
-    dst: &mut [i16; HL],
-    weights: &[[i16; HL]; INPUT],
+    dst: &mut [i16],
+    weights: &[i16],
     add1: usize,
     add2: usize,
     add3: usize,
     add4: usize,
 ) {
-    let add1: &[i16; HL] = &weights[add1];
-    let add2: &[i16; HL] = &weights[add2];
-    let add3: &[i16; HL] = &weights[add3];
-    let add4: &[i16; HL] = &weights[add4];
+    let hl = dst.len();
+    let add1 = tiles(row(weights, add1, hl));
+    let add2 = tiles(row(weights, add2, hl));
+    let add3 = tiles(row(weights, add3, hl));
+    let add4 = tiles(row(weights, add4, hl));
 
-    for i in 0..HL {
-        dst[i] += add1[i] + add2[i] + add3[i] + add4[i];
+    for (t, dst) in tiles_mut(dst).iter_mut().enumerate() {
+        for i in 0..HL_ALIGN {
+            dst[i] += add1[t][i] + add2[t][i] + add3[t][i] + add4[t][i];
+        }
     }
 }
 
 pub fn acc_sub4(
-    dst: &mut [i16; HL],
-    weights: &[[i16; HL]; INPUT],
+    dst: &mut [i16],
+    weights: &[i16],
     sub1: usize,
     sub2: usize,
     sub3: usize,
     sub4: usize,
 ) {
-    let sub1: &[i16; HL] = &weights[sub1];
-    let sub2: &[i16; HL] = &weights[sub2];
-    let sub3: &[i16; HL] = &weights[sub3];
-    let sub4: &[i16; HL] = &weights[sub4];
+    let hl = dst.len();
+    let sub1 = tiles(row(weights, sub1, hl));
+    let sub2 = tiles(row(weights, sub2, hl));
+    let sub3 = tiles(row(weights, sub3, hl));
+    let sub4 = tiles(row(weights, sub4, hl));
+
+    for (t, dst) in tiles_mut(dst).iter_mut().enumerate() {
+        for i in 0..HL_ALIGN {
+            dst[i] += -sub1[t][i] - sub2[t][i] - sub3[t][i] - sub4[t][i];
+        }
+    }
+}
+
+/// Computes `dst = src + adds - subs` for any number of features. The accumulator is processed
+/// tile by tile, so that a tile stays in registers while all weight rows are added to it, and
+/// every neuron of `dst` is written only once.
+pub fn acc_fused(src: &[i16], dst: &mut [i16], weights: &[i16], adds: &[usize], subs: &[usize]) {
+    let hl = src.len();
+
+    for (tile, (src, dst)) in tiles(src).iter().zip(tiles_mut(dst)).enumerate() {
+        let row = |feature: usize| &tiles(row(weights, feature, hl))[tile];
+        let mut values = *src;
+
+        for &add in adds {
+            let add = row(add);
+            for i in 0..HL_ALIGN {
+                values[i] += add[i];
+            }
+        }
+        for &sub in subs {
+            let sub = row(sub);
+            for i in 0..HL_ALIGN {
+                values[i] -= sub[i];
+            }
+        }
 
-    for i in 0..HL {
-        dst[i] += -sub1[i] - sub2[i] - sub3[i] - sub4[i];
+        *dst = values;
     }
 }
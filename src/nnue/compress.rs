@@ -0,0 +1,118 @@
+//! Compression of net files, used for the embedded net and for nets loaded at runtime. Most
+//! weights are small, so the net is stored as a stream of `i16`s, each zigzag encoded as a LEB128
+//! varint. Runs of zeros (mostly padding and dead neurons) are stored as a zero byte followed by
+//! the length of the run minus one. Since a nonzero value never starts with a zero byte, the two
+//! can't be confused.
+//!
+//! This file is also included by `build.rs`, so it may only use `std`.
+
+/// Magic bytes at the start of a compressed net, followed by a version byte, three zero bytes,
+/// and the uncompressed size as a little endian `u64`.
+pub const COMPRESSED_MAGIC: [u8; 4] = *b"ICNZ";
+const COMPRESSED_VERSION: u8 = 1;
+const PREFIX_SIZE: usize = 16;
+
+pub fn is_compressed(bytes: &[u8]) -> bool {
+    bytes.starts_with(&COMPRESSED_MAGIC)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Result<u32, String> {
+    let mut value = 0u32;
+    for shift in (0..32).step_by(7) {
+        let &byte = bytes.get(*pos).ok_or("compressed net is truncated")?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("invalid varint in compressed net".to_string())
+}
+
+/// Compresses `bytes`, whose length has to be even.
+pub fn compress(bytes: &[u8]) -> Vec<u8> {
+    assert!(bytes.len().is_multiple_of(2));
+
+    let mut out = Vec::with_capacity(bytes.len() / 2);
+    out.extend(COMPRESSED_MAGIC);
+    out.extend([COMPRESSED_VERSION, 0, 0, 0]);
+    out.extend((bytes.len() as u64).to_le_bytes());
+
+    let values: Vec<i16> = bytes
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        .collect();
+
+    let mut i = 0;
+    while i < values.len() {
+        if values[i] == 0 {
+            let run = values[i..].iter().take_while(|&&v| v == 0).count();
+            out.push(0);
+            write_varint(&mut out, run as u32 - 1);
+            i += run;
+        } else {
+            let v = values[i];
+            write_varint(&mut out, ((v << 1) ^ (v >> 15)) as u16 as u32);
+            i += 1;
+        }
+    }
+
+    out
+}
+
+/// Reverses `compress`.
+pub fn decompress(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    if bytes.len() < PREFIX_SIZE || !is_compressed(bytes) {
+        return Err("not a compressed net".to_string());
+    }
+    if bytes[4] != COMPRESSED_VERSION {
+        return Err(format!("unsupported compressed net version {}", bytes[4]));
+    }
+    let size = u64::from_le_bytes(bytes[8..16].try_into().unwrap()) as usize;
+
+    let mut out = Vec::with_capacity(size);
+    let mut pos = PREFIX_SIZE;
+    while pos < bytes.len() && out.len() < size {
+        if bytes[pos] == 0 {
+            pos += 1;
+            let run = read_varint(bytes, &mut pos)? as usize + 1;
+            out.resize(out.len() + 2 * run, 0);
+        } else {
+            let zigzag = read_varint(bytes, &mut pos)?;
+            let v = ((zigzag >> 1) as i32 ^ -((zigzag & 1) as i32)) as i16;
+            out.extend(v.to_le_bytes());
+        }
+    }
+
+    if out.len() != size || pos != bytes.len() {
+        return Err(format!(
+            "compressed net decompresses to {} bytes, expected {size}",
+            out.len()
+        ));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress, decompress};
+
+    #[test]
+    fn roundtrip() {
+        let mut values = vec![0, 0, 0, 1, -1, 63, -64, 64, 300];
+        values.extend([i16::MIN, i16::MAX, 0, 5, 0, 0]);
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+
+        let compressed = compress(&bytes);
+        assert_eq!(decompress(&compressed).unwrap(), bytes);
+        assert!(decompress(&compressed[..compressed.len() - 1]).is_err());
+    }
+}
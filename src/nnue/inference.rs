@@ -2,14 +2,12 @@
 
 use cfg_if::cfg_if;
 
-use crate::nnue::network::NET;
+use crate::nnue::network::Network;
 
-use super::network::HL;
+pub const QA: i16 = 255;
+pub const QB: i16 = 64;
 
-const QA: i16 = 255;
-const QB: i16 = 64;
-
-const SCALE: i32 = 400;
+pub const SCALE: i32 = 400;
 
 cfg_if!(
     if #[cfg(target_feature = "avx512bw")] {
@@ -23,3 +21,25 @@ cfg_if!(
         pub use generic::forward;
     }
 );
+
+/// Win, draw and loss probabilities from the WDL head of `net`. This is only used for printing
+/// and at the root, so it doesn't need SIMD.
+pub fn forward_wdl(net: &Network, us: &[i16], them: &[i16]) -> [f64; 3] {
+    let logits = std::array::from_fn::<_, 3, _>(|i| {
+        let mut output = 0i64;
+        for (side, values) in [us, them].into_iter().enumerate() {
+            let weights = net.wdl_weights(i, side);
+            for (&value, &weight) in values.iter().zip(weights) {
+                let clamped = i64::from(value.clamp(0, QA));
+                output += clamped * clamped * i64::from(weight);
+            }
+        }
+        let output = output / i64::from(QA) + i64::from(net.wdl_bias[i]);
+        output as f64 / (f64::from(QA) * f64::from(QB))
+    });
+
+    let max = logits.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let exps = logits.map(|logit| (logit - max).exp());
+    let sum: f64 = exps.iter().sum();
+    exps.map(|e| e / sum)
+}
@@ -1,28 +1,29 @@
 use super::*;
 use std::arch::x86_64::*;
 
-pub fn forward(us: &[i16; HL], them: &[i16; HL]) -> i32 {
-    unsafe { forward_impl(us, them) }
+pub fn forward(net: &Network, us: &[i16], them: &[i16]) -> i32 {
+    // The loads rely on this. `net.hl` is a multiple of `HL_ALIGN`, so there is no remainder.
+    assert!(us.len() == net.hl && them.len() == net.hl);
+    unsafe { forward_impl(net, us, them) }
 }
 
 #[target_feature(enable = "avx2")]
-fn forward_impl(us: &[i16; HL], them: &[i16; HL]) -> i32 {
-    const { assert!(HL.is_multiple_of(64)) };
+fn forward_impl(net: &Network, us: &[i16], them: &[i16]) -> i32 {
     let zero = _mm256_setzero_si256();
     let qa = _mm256_set1_epi16(QA);
 
     let us_ptr = us.as_ptr().cast::<__m256i>();
     let them_ptr = them.as_ptr().cast::<__m256i>();
 
-    let us_weights = NET.out_weight[0].as_ptr().cast::<__m256i>();
-    let them_weights = NET.out_weight[1].as_ptr().cast::<__m256i>();
+    let us_weights = net.out_weights(0).as_ptr().cast::<__m256i>();
+    let them_weights = net.out_weights(1).as_ptr().cast::<__m256i>();
 
     let mut sums0 = _mm256_setzero_si256();
     let mut sums1 = _mm256_setzero_si256();
     let mut sums2 = _mm256_setzero_si256();
     let mut sums3 = _mm256_setzero_si256();
 
-    for i in 0..HL / 64 {
+    for i in 0..net.hl / 64 {
         let us0 = unsafe { _mm256_loadu_si256(us_ptr.add(4 * i + 0)) };
         let us1 = unsafe { _mm256_loadu_si256(us_ptr.add(4 * i + 1)) };
         let us2 = unsafe { _mm256_loadu_si256(us_ptr.add(4 * i + 2)) };
@@ -103,7 +104,7 @@ fn forward_impl(us: &[i16; HL], them: &[i16; HL]) -> i32 {
     let mut output = reduce_sum(sums);
 
     output /= i32::from(QA);
-    output += i32::from(NET.out_bias);
+    output += i32::from(net.out_bias);
 
     output *= SCALE;
 
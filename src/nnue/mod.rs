@@ -1,3 +1,6 @@
 pub mod accumulator;
+pub mod compress;
 pub mod inference;
+pub mod netinfo;
 pub mod network;
+pub mod transform;
@@ -0,0 +1,296 @@
+//! The `netinfo` command, which prints statistics about a network that are worth checking before
+//! shipping it: weight ranges per king bucket, quantisation saturation, dead hidden neurons and
+//! the risk of overflowing the `i16` accumulators.
+
+use std::{borrow::Cow, path::Path};
+
+use icarus_board::board::Board;
+use icarus_common::piece::{Color, Piece};
+
+use crate::{
+    bench::{FENS, read_positions},
+    nnue::{
+        accumulator::{Feature, threat_features, threats},
+        inference::QA,
+        network::{INPUT, NET, Network},
+    },
+};
+
+/// Number of buckets of the activation histogram.
+const HIST_BUCKETS: usize = 8;
+/// Width of the longest histogram bar.
+const BAR_WIDTH: u64 = 50;
+
+/// How often each hidden neuron was active over a sample of accumulators.
+pub struct ActivationStats {
+    /// Number of accumulators in the sample. Every position contributes one per perspective.
+    pub samples: u64,
+    /// `active[i]` is the number of accumulators in which neuron `i` was above zero.
+    pub active: Vec<u64>,
+    /// Number of activations per value range, with `HIST_BUCKETS` ranges up to `QA`.
+    histogram: [u64; HIST_BUCKETS],
+    /// Smallest and largest accumulator value seen, without the `i16` wraparound.
+    acc_range: (i32, i32),
+}
+
+impl ActivationStats {
+    /// Collects activations over `boards` and all positions reachable from them in one move,
+    /// so that even a short list of positions gives a reasonably large sample.
+    pub fn collect(net: &Network, boards: &[Board]) -> Self {
+        let mut stats = Self {
+            samples: 0,
+            active: vec![0; net.hl],
+            histogram: [0; HIST_BUCKETS],
+            acc_range: (i32::MAX, i32::MIN),
+        };
+
+        for board in boards {
+            stats.add(net, board);
+            for mv in board.gen_all_moves_to::<Vec<_>>() {
+                let mut child = *board;
+                child.make_move(mv);
+                stats.add(net, &child);
+            }
+        }
+
+        stats
+    }
+
+    fn add(&mut self, net: &Network, board: &Board) {
+        for perspective in Color::all() {
+            let acc = wide_accumulator(net, board, perspective);
+            self.samples += 1;
+
+            for (i, &value) in acc.iter().enumerate() {
+                self.acc_range = (self.acc_range.0.min(value), self.acc_range.1.max(value));
+
+                let clamped = value.clamp(0, QA as i32);
+                if clamped > 0 {
+                    self.active[i] += 1;
+                }
+                let bucket = clamped as usize * HIST_BUCKETS / (QA as usize + 1);
+                self.histogram[bucket] += 1;
+            }
+        }
+    }
+}
+
+/// Computes the accumulator of `perspective` from scratch, in `i32` so that values which would
+/// overflow the real accumulator show up.
+fn wide_accumulator(net: &Network, board: &Board, perspective: Color) -> Vec<i32> {
+    let king = board.king(perspective);
+    let mirror = net.header.should_mirror(king);
+    let weights = net.ft_weights(net.header.king_bucket(king, perspective));
+    let mut acc: Vec<i32> = net.ft_bias.iter().map(|&b| b as i32).collect();
+
+    for color in Color::all() {
+        for piece in Piece::all() {
+            for square in board.colored_pieces(piece, color) {
+                let feature = Feature {
+                    piece,
+                    color,
+                    square,
+                };
+                let row = &weights[feature.idx(perspective, mirror) * net.hl..][..net.hl];
+                for (acc, &w) in acc.iter_mut().zip(row) {
+                    *acc += w as i32;
+                }
+            }
+        }
+    }
+
+    if let Some(threat_weight) = &net.threat_weight {
+        for feature in threat_features(&threats(board.piece_bbs(), board.color_bbs())) {
+            let row = &threat_weight[feature.idx(perspective, mirror) * net.hl..][..net.hl];
+            for (acc, &w) in acc.iter_mut().zip(row) {
+                *acc += w as i32;
+            }
+        }
+    }
+
+    acc
+}
+
+/// Loads the net at `net_path` (or borrows the embedded one), and the sample positions from
+/// `positions` (or `bench::FENS`). Errors are printed as info strings.
+pub fn load_inputs(
+    net_path: Option<&Path>,
+    positions: Option<&Path>,
+) -> Option<(Cow<'static, Network>, Vec<Board>)> {
+    let net = match net_path {
+        Some(path) => match Network::load(path) {
+            Ok(net) => Cow::Owned(*net),
+            Err(e) => {
+                println!("info string Failed to load net `{}`: {e}", path.display());
+                return None;
+            }
+        },
+        None => Cow::Borrowed(*NET),
+    };
+    let boards = match positions {
+        Some(path) => match read_positions(path) {
+            Ok(boards) => boards,
+            Err(e) => {
+                println!("info string {e}");
+                return None;
+            }
+        },
+        None => FENS
+            .iter()
+            .map(|fen| Board::read_fen(fen).unwrap())
+            .collect(),
+    };
+
+    Some((net, boards))
+}
+
+pub fn netinfo(net_path: Option<&Path>, positions: Option<&Path>) {
+    let Some((net, boards)) = load_inputs(net_path, positions) else {
+        return;
+    };
+
+    print_weights(&net);
+    println!();
+
+    let stats = ActivationStats::collect(&net, &boards);
+    print_activations(&stats);
+}
+
+fn print_weights(net: &Network) {
+    let header = &net.header;
+    let mirror = if header.mirror != 0 {
+        "mirrored while the king is on files E to H"
+    } else {
+        "not mirrored"
+    };
+    println!("King buckets: {}, {mirror}", header.num_king_buckets);
+    for rank in header.king_bucket_layout.chunks(8).rev() {
+        let rank: Vec<String> = rank.iter().map(|b| format!("{b:>3}")).collect();
+        println!("{}", rank.join(""));
+    }
+    println!();
+
+    println!("Feature transformer weights per king bucket:");
+    println!("bucket      min      max  mean |w|  saturated");
+
+    let mut means = vec![];
+    for (bucket, weights) in net.ft_weight.chunks(INPUT * net.hl).enumerate() {
+        let values = weights.iter().copied();
+        let (min, max) = values
+            .clone()
+            .fold((i16::MAX, i16::MIN), |(lo, hi), w| (lo.min(w), hi.max(w)));
+        let mean =
+            values.clone().map(|w| w.unsigned_abs() as f64).sum::<f64>() / (INPUT * net.hl) as f64;
+        let saturated = values.filter(|&w| w == i16::MIN || w == i16::MAX).count();
+
+        println!("{bucket:>6} {min:>8} {max:>8} {mean:>9.2} {saturated:>10}");
+        means.push(mean);
+    }
+
+    // Buckets whose weights are much larger or smaller than usual are likely undertrained.
+    let mut sorted = means.clone();
+    sorted.sort_by(f64::total_cmp);
+    let median = sorted[sorted.len() / 2];
+    for (bucket, &mean) in means.iter().enumerate() {
+        if !(0.5 * median..=2.0 * median).contains(&mean) {
+            println!("Warning: bucket {bucket} has a mean |w| of {mean:.2}, median is {median:.2}");
+        }
+    }
+
+    let range = |values: &[i16]| {
+        let min = values.iter().min().unwrap();
+        let max = values.iter().max().unwrap();
+        format!("{min}..={max}")
+    };
+    println!();
+    match &net.threat_weight {
+        Some(threat_weight) => println!("Threat weights:           {}", range(threat_weight)),
+        None => println!("Threat inputs:            none"),
+    }
+    println!("Feature transformer bias: {}", range(&net.ft_bias));
+    println!("Output weights (us):      {}", range(net.out_weights(0)));
+    println!("Output weights (them):    {}", range(net.out_weights(1)));
+    println!("Output bias:              {}", net.out_bias);
+    if net.header.wdl_head != 0 {
+        println!("WDL head weights:         {}", range(&net.wdl_weight));
+        println!("WDL head biases:          {:?}", net.wdl_bias);
+    } else {
+        println!("WDL head:                 none");
+    }
+
+    // The output layer multiplies clamped activations of up to `QA` with the output weights in
+    // `i16`, which overflows for larger weights.
+    let max_out = i16::MAX / QA;
+    let overflowing = net
+        .out_weight
+        .iter()
+        .filter(|w| w.unsigned_abs() > max_out as u16)
+        .count();
+    println!("Output weights above {max_out} (overflow QA * w): {overflowing}");
+
+    // The worst case accumulator adds the 32 largest (or smallest) weights of any input to the
+    // bias. This is far from a real position, so it's only an upper bound.
+    let (mut worst_lo, mut worst_hi) = (0i32, 0i32);
+    let mut column = [0i16; INPUT];
+    for weights in net.ft_weight.chunks(INPUT * net.hl) {
+        for i in 0..net.hl {
+            for (value, row) in column.iter_mut().zip(weights.chunks(net.hl)) {
+                *value = row[i];
+            }
+            column.sort_unstable();
+            let bias = net.ft_bias[i] as i32;
+            let lo = bias + column[..32].iter().map(|&w| w as i32).sum::<i32>();
+            let hi = bias + column[INPUT - 32..].iter().map(|&w| w as i32).sum::<i32>();
+            worst_lo = worst_lo.min(lo);
+            worst_hi = worst_hi.max(hi);
+        }
+    }
+    println!(
+        "Worst case accumulator bound: {worst_lo}..={worst_hi} (i16 is {}..={})",
+        i16::MIN,
+        i16::MAX
+    );
+}
+
+fn print_activations(stats: &ActivationStats) {
+    let (lo, hi) = stats.acc_range;
+    println!(
+        "Sampled {} accumulators, values {lo}..={hi}{}",
+        stats.samples,
+        if lo < i16::MIN as i32 || hi > i16::MAX as i32 {
+            " (overflows i16!)"
+        } else {
+            ""
+        }
+    );
+
+    let dead = stats.active.iter().filter(|&&n| n == 0).count();
+    let always = stats.active.iter().filter(|&&n| n == stats.samples).count();
+    let hl = stats.active.len();
+    println!("Neurons never active:  {dead}/{hl}");
+    println!("Neurons always active: {always}/{hl}");
+
+    println!();
+    println!("Activation histogram (clamped to 0..={QA}):");
+    print_histogram(&stats.histogram, |i| {
+        let width = (QA as usize + 1) / HIST_BUCKETS;
+        format!("{:>3}..{:>3}", i * width, (i + 1) * width)
+    });
+
+    println!();
+    println!("Neurons by fraction of samples in which they are active:");
+    let mut freq = [0u64; 10];
+    for &n in &stats.active {
+        let i = (n * 10 / stats.samples.max(1)).min(9) as usize;
+        freq[i] += 1;
+    }
+    print_histogram(&freq, |i| format!("{:>3}%..{:>3}%", i * 10, (i + 1) * 10));
+}
+
+fn print_histogram(counts: &[u64], label: impl Fn(usize) -> String) {
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+    for (i, &count) in counts.iter().enumerate() {
+        let bar = "#".repeat((count * BAR_WIDTH / max) as usize);
+        println!("{} {count:>10} {bar}", label(i));
+    }
+}
@@ -1,3 +1,5 @@
+use std::{fs, io, ops::Deref, path::Path, sync::LazyLock};
+
 use arrayvec::ArrayVec;
 use icarus_board::{
     board::Board,
@@ -6,26 +8,33 @@ use icarus_board::{
 use icarus_common::{
     piece::{Color, Piece},
     square::{File, Rank, Square},
-    util::enum_map::enum_map,
+    util::enum_map::{EnumMap, enum_map},
 };
 
 use crate::{
     nnue::{
         accumulator::{
             Accumulator, Feature, KingBucketCache, Updates, acc_add, acc_add_sub, acc_add_sub2,
-            acc_add2_sub2, acc_add4, acc_sub, acc_sub4,
+            acc_add2_sub2, acc_add4, acc_fused, acc_sub, acc_sub4, acc_threat_diff,
+            threat_features, threats,
         },
-        inference::forward,
+        compress::{decompress, is_compressed},
+        inference::{forward, forward_wdl},
     },
     util::MAX_PLY,
 };
 
 // 704 instead of 768 because of king plane merging
 pub const INPUT: usize = 704;
-pub const HL: usize = 1024;
-pub const NUM_KING_BUCKETS: usize = 14;
+/// The hidden layer size has to be a multiple of this for the SIMD inference code.
+pub const HL_ALIGN: usize = 128;
+/// Size of the `NetHeader` at the start of a net file.
+pub const HEADER_SIZE: usize = 128;
+pub const NET_MAGIC: [u8; 4] = *b"ICNN";
+pub const NET_VERSION: u8 = 1;
+/// The king buckets of nets without a header, which are mirrored horizontally.
 #[rustfmt::skip]
-pub static KING_BUCKET_LAYOUT: [u8; 64] = [
+pub static DEFAULT_KING_BUCKET_LAYOUT: [u8; 64] = [
      0,  1,  2,  3,  3,  2,  1,  0,
      4,  5,  6,  7,  7,  6,  5,  4,
      8,  8,  9,  9,  9,  9,  8,  8,
@@ -36,68 +45,336 @@ pub static KING_BUCKET_LAYOUT: [u8; 64] = [
     12, 12, 13, 13, 13, 13, 12, 12,
 ];
 
-pub fn king_bucket(king: Square, perspective: Color) -> usize {
-    let king = Square::new(king.file(), king.rank().relative_to(perspective));
-    KING_BUCKET_LAYOUT[king] as usize
+/// Describes how a net buckets the king squares. Nets from before the header existed get
+/// `NetHeader::DEFAULT` prepended, both by `build.rs` and by `Network::load`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct NetHeader {
+    pub magic: [u8; 4],
+    pub version: u8,
+    pub num_king_buckets: u8,
+    /// 1 if the board is mirrored horizontally while the king is on files E to H, 0 if not.
+    pub mirror: u8,
+    /// 1 if the net has a WDL head after the output layer, 0 if not.
+    pub wdl_head: u8,
+    /// 1 if the net has a block of threat inputs after the regular feature weights, 0 if not.
+    pub threat_inputs: u8,
+    padding: [u8; 55],
+    /// The bucket of every king square, from the perspective of the king's side.
+    pub king_bucket_layout: [u8; 64],
 }
 
-pub fn should_mirror(king: Square) -> bool {
-    king.file() > File::D
+const _: () = assert!(size_of::<NetHeader>() == HEADER_SIZE);
+
+impl NetHeader {
+    pub const DEFAULT: Self = Self {
+        magic: NET_MAGIC,
+        version: NET_VERSION,
+        num_king_buckets: 14,
+        mirror: 1,
+        wdl_head: 0,
+        threat_inputs: 0,
+        padding: [0; 55],
+        king_bucket_layout: DEFAULT_KING_BUCKET_LAYOUT,
+    };
+
+    /// Reads the header at the start of `bytes`, if there is one.
+    pub fn read(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < HEADER_SIZE || !bytes.starts_with(&NET_MAGIC) {
+            return None;
+        }
+        // SAFETY: `NetHeader` only consists of bytes, so any bytes are valid, and `bytes` is long
+        // enough.
+        Some(unsafe { bytes.as_ptr().cast::<Self>().read_unaligned() })
+    }
+
+    pub fn to_bytes(self) -> [u8; HEADER_SIZE] {
+        // SAFETY: `NetHeader` has no padding bytes, and is `HEADER_SIZE` bytes large.
+        unsafe { std::mem::transmute(self) }
+    }
+
+    /// Checks that the header can be used with this build.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.version != NET_VERSION {
+            return Err(format!("unsupported net version {}", self.version));
+        }
+        if self.num_king_buckets == 0 {
+            return Err("the net has no king buckets".to_string());
+        }
+        if self.mirror > 1 {
+            return Err(format!("invalid mirroring mode {}", self.mirror));
+        }
+        if self.wdl_head > 1 {
+            return Err(format!("invalid WDL head flag {}", self.wdl_head));
+        }
+        if self.threat_inputs > 1 {
+            return Err(format!("invalid threat inputs flag {}", self.threat_inputs));
+        }
+        if let Some(&b) = self
+            .king_bucket_layout
+            .iter()
+            .find(|&&b| b >= self.num_king_buckets)
+        {
+            return Err(format!("king bucket {b} is out of range"));
+        }
+        Ok(())
+    }
+
+    pub fn king_bucket(&self, king: Square, perspective: Color) -> usize {
+        let king = Square::new(king.file(), king.rank().relative_to(perspective));
+        self.king_bucket_layout[king] as usize
+    }
+
+    pub fn should_mirror(&self, king: Square) -> bool {
+        self.mirror != 0 && king.file() > File::D
+    }
+
+    /// Whether a king move from `from` to `to` changes the bucket or mirroring, so that the
+    /// accumulator of its side has to be refreshed.
+    pub fn needs_refresh(&self, from: Square, to: Square, perspective: Color) -> bool {
+        self.king_bucket(from, perspective) != self.king_bucket(to, perspective)
+            || self.should_mirror(from) != self.should_mirror(to)
+    }
 }
 
-pub static NET: Network =
-    unsafe { std::mem::transmute(*include_bytes!(concat!(env!("OUT_DIR"), "/icarus.nnue"))) };
+/// The embedded net. `build.rs` stores it compressed, so it's decompressed on first use.
+pub static NET: LazyLock<&'static Network> = LazyLock::new(|| {
+    let bytes = include_bytes!(concat!(env!("OUT_DIR"), "/icarus.nnue")).to_vec();
+    Box::leak(Network::from_bytes(bytes).expect("embedded net is invalid"))
+});
 
+/// `HL_ALIGN` weights, aligned to a cache line.
 #[repr(C, align(64))]
+#[derive(Clone, Copy)]
+struct Tile([i16; HL_ALIGN]);
+
+/// Weights that start at a cache line. Every row of a weight matrix is a multiple of `HL_ALIGN`
+/// long, so all rows are aligned as well, and SIMD loads from them never cross a cache line.
+#[derive(Clone)]
+pub struct Weights {
+    tiles: Box<[Tile]>,
+    len: usize,
+}
+
+impl Deref for Weights {
+    type Target = [i16];
+
+    fn deref(&self) -> &[i16] {
+        // SAFETY: `Tile` is an array of `i16` without padding, and `tiles` holds at least `len`
+        // values.
+        unsafe { std::slice::from_raw_parts(self.tiles.as_ptr().cast(), self.len) }
+    }
+}
+
+impl FromIterator<i16> for Weights {
+    fn from_iter<I: IntoIterator<Item = i16>>(iter: I) -> Self {
+        let values: Vec<i16> = iter.into_iter().collect();
+        let mut tiles = vec![Tile([0; HL_ALIGN]); values.len().div_ceil(HL_ALIGN)];
+        for (tile, chunk) in tiles.iter_mut().zip(values.chunks(HL_ALIGN)) {
+            tile.0[..chunk.len()].copy_from_slice(chunk);
+        }
+        Self {
+            tiles: tiles.into_boxed_slice(),
+            len: values.len(),
+        }
+    }
+}
+
+/// A network of any hidden layer size and number of king buckets, which are taken from the net
+/// file when it's loaded.
+#[derive(Clone)]
 pub struct Network {
-    pub ft_weight: [[[i16; HL]; INPUT]; NUM_KING_BUCKETS],
-    pub ft_bias: [i16; HL],
-    pub out_weight: [[i16; HL]; 2],
+    pub header: NetHeader,
+    /// Size of the hidden layer, a multiple of `HL_ALIGN`.
+    pub hl: usize,
+    /// Indexed by `[bucket][input][neuron]`.
+    pub ft_weight: Weights,
+    /// Weights of the threat features, which aren't king bucketed. Indexed by `[input][neuron]`.
+    /// `None` if the net doesn't have threat inputs.
+    pub threat_weight: Option<Weights>,
+    pub ft_bias: Weights,
+    /// Indexed by `[side][neuron]`.
+    pub out_weight: Weights,
     pub out_bias: i16,
+    /// Weights of the optional WDL head, indexed by `[outcome][side][neuron]`. Empty if the net
+    /// doesn't have one.
+    pub wdl_weight: Weights,
+    pub wdl_bias: [i16; 3],
+}
+
+impl Network {
+    /// Loads a network from a file. Nets used for searching have to be leaked, so that they can
+    /// be shared by any number of threads like the embedded one.
+    pub fn load(path: &Path) -> io::Result<Box<Network>> {
+        Self::from_bytes(fs::read(path)?)
+    }
+
+    /// Creates a network from the contents of a net file, which may be compressed and doesn't
+    /// need to have a header. The hidden layer size follows from the file size.
+    pub fn from_bytes(mut bytes: Vec<u8>) -> io::Result<Box<Network>> {
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+
+        if is_compressed(&bytes) {
+            bytes = decompress(&bytes).map_err(invalid)?;
+        }
+
+        let header = match NetHeader::read(&bytes) {
+            Some(header) => header,
+            None => {
+                bytes.splice(0..0, NetHeader::DEFAULT.to_bytes());
+                NetHeader::DEFAULT
+            }
+        };
+        header.validate().map_err(invalid)?;
+
+        // Every neuron has a weight per input and king bucket, a weight per threat input if
+        // enabled, a bias, two output weights and six WDL head weights if there is one.
+        let size = bytes.len();
+        let hl = size.saturating_sub(Self::fixed_size(&header)) / Self::neuron_size(&header);
+        if hl == 0 || !hl.is_multiple_of(HL_ALIGN) || Self::file_size(&header, hl) != size {
+            return Err(invalid(format!(
+                "{size} bytes don't match any hidden layer size that's a multiple of {HL_ALIGN}{}",
+                if header.threat_inputs != 0 {
+                    " with threat inputs"
+                } else {
+                    ""
+                }
+            )));
+        }
+
+        let mut values = bytes[HEADER_SIZE..]
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]));
+        let mut take = |n: usize| -> Weights { values.by_ref().take(n).collect() };
+        let buckets = header.num_king_buckets as usize;
+        let has_wdl = header.wdl_head != 0;
+
+        let net = Network {
+            header,
+            hl,
+            ft_weight: take(buckets * INPUT * hl),
+            threat_weight: (header.threat_inputs != 0).then(|| take(INPUT * hl)),
+            ft_bias: take(hl),
+            out_weight: take(2 * hl),
+            out_bias: take(1)[0],
+            wdl_weight: take(if has_wdl { 6 * hl } else { 0 }),
+            wdl_bias: if has_wdl {
+                take(3)[..].try_into().unwrap()
+            } else {
+                [0; 3]
+            },
+        };
+        Ok(Box::new(net))
+    }
+
+    /// Serializes the net in the format read by `from_bytes`, with a header.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let wdl: &[i16] = if self.header.wdl_head != 0 {
+            &[&self.wdl_weight[..], &self.wdl_bias].concat()
+        } else {
+            &[]
+        };
+        let weights = [Some(&self.ft_weight), self.threat_weight.as_ref()]
+            .into_iter()
+            .flatten()
+            .chain([&self.ft_bias, &self.out_weight])
+            .flat_map(|v| v.iter())
+            .chain([&self.out_bias])
+            .chain(wdl)
+            .flat_map(|v| v.to_le_bytes());
+
+        let mut bytes: Vec<u8> = self.header.to_bytes().into_iter().chain(weights).collect();
+        bytes.resize(Self::file_size(&self.header, self.hl), 0);
+        bytes
+    }
+
+    /// Size of the weights of a single hidden neuron in a net file.
+    fn neuron_size(header: &NetHeader) -> usize {
+        let threat_inputs = if header.threat_inputs != 0 { INPUT } else { 0 };
+        let wdl_weights = if header.wdl_head != 0 { 6 } else { 0 };
+        let buckets = header.num_king_buckets as usize;
+        2 * (buckets * INPUT + threat_inputs + 1 + 2 + wdl_weights)
+    }
+
+    /// Size of the parts of a net file that don't depend on the hidden layer size, which are the
+    /// header and the output biases.
+    fn fixed_size(header: &NetHeader) -> usize {
+        let biases = if header.wdl_head != 0 { 4 } else { 1 };
+        HEADER_SIZE + 2 * biases
+    }
+
+    /// Size of a net file with `hl` hidden neurons, which is padded to a multiple of 64 bytes.
+    fn file_size(header: &NetHeader, hl: usize) -> usize {
+        (Self::fixed_size(header) + hl * Self::neuron_size(header)).next_multiple_of(64)
+    }
+
+    /// The feature weights of king bucket `bucket`, indexed by `[input][neuron]`.
+    pub fn ft_weights(&self, bucket: usize) -> &[i16] {
+        &self.ft_weight[bucket * INPUT * self.hl..][..INPUT * self.hl]
+    }
+
+    /// The output weights of the accumulator of the side to move (`side == 0`) or the other side.
+    pub fn out_weights(&self, side: usize) -> &[i16] {
+        &self.out_weight[side * self.hl..][..self.hl]
+    }
+
+    /// Like `out_weights`, but for the WDL head output `outcome` (win, draw or loss).
+    pub fn wdl_weights(&self, outcome: usize, side: usize) -> &[i16] {
+        &self.wdl_weight[(outcome * 2 + side) * self.hl..][..self.hl]
+    }
 }
 
 pub struct Nnue {
-    stack: Box<[Accumulator; MAX_PLY as usize + 1]>,
+    net: &'static Network,
+    stack: Box<[Accumulator]>,
     idx: usize,
-    cache: Box<KingBucketCache>,
+    cache: KingBucketCache,
 }
 
 impl Nnue {
     pub fn new(board: &Board) -> Self {
-        let mut this = Self {
-            stack: vec![
-                Accumulator {
-                    values: enum_map! { _ => [0; HL] },
-                    dirty: Default::default(),
-                    needs_refresh: Default::default(),
-                    updates: Default::default(),
-                };
-                MAX_PLY as usize + 1
-            ]
-            .into_boxed_slice()
-            .try_into()
-            .unwrap(),
-            idx: 0,
-            cache: Default::default(),
-        };
-
+        let mut this = Self::with_net(*NET);
         this.full_reset(board);
         this
     }
 
+    /// Allocates the accumulators for the hidden layer and king buckets of `net`. They have to
+    /// be reset before evaluating anything.
+    fn with_net(net: &'static Network) -> Self {
+        Self {
+            net,
+            stack: vec![Accumulator::new(net.hl); MAX_PLY as usize + 1].into_boxed_slice(),
+            idx: 0,
+            cache: KingBucketCache::new(net),
+        }
+    }
+
+    pub fn has_wdl(&self) -> bool {
+        self.net.header.wdl_head != 0
+    }
+
+    /// Makes this use `net` instead of the embedded network. Since `net` may have a different
+    /// size, the accumulators are reallocated, and need a `full_reset` before the next eval.
+    pub fn set_net(&mut self, net: &'static Network) {
+        *self = Self::with_net(net);
+    }
+
     pub fn full_reset(&mut self, board: &Board) {
         self.idx = 0;
-        *self.cache = Default::default();
+        self.cache.clear(self.net);
+        if self.net.threat_weight.is_some() {
+            self.stack[0].threats = threats(board.piece_bbs(), board.color_bbs());
+        }
         self.reset(board, Color::White);
         self.reset(board, Color::Black);
     }
 
     pub fn reset(&mut self, board: &Board, perspective: Color) {
         let king = board.king(perspective);
-        let mirror = should_mirror(king);
-        let bucket = king_bucket(king, perspective);
+        let mirror = self.net.header.should_mirror(king);
+        let bucket = self.net.header.king_bucket(king, perspective);
 
-        let entry = &mut self.cache.entries[perspective][mirror as usize][bucket];
+        let entry = self.cache.entry(perspective, mirror, bucket);
 
         let mut adds: ArrayVec<usize, 64> = ArrayVec::new();
         let mut subs: ArrayVec<usize, 64> = ArrayVec::new();
@@ -114,7 +391,7 @@ impl Nnue {
                             color,
                             square: add,
                         }
-                        .idx(perspective, king),
+                        .idx(perspective, mirror),
                     );
                 }
 
@@ -125,13 +402,13 @@ impl Nnue {
                             color,
                             square: sub,
                         }
-                        .idx(perspective, king),
+                        .idx(perspective, mirror),
                     );
                 }
             }
         }
 
-        let weights = &NET.ft_weight[bucket];
+        let weights = self.net.ft_weights(bucket);
         let values = &mut entry.features;
 
         let (chunks, rem) = adds.as_chunks();
@@ -154,6 +431,19 @@ impl Nnue {
         entry.colors = *board.color_bbs();
 
         self.stack[self.idx].values[perspective].copy_from_slice(&entry.features);
+
+        // Threats depend on the whole position, so they aren't cached.
+        if let Some(threat_weight) = &self.net.threat_weight {
+            let acc = &mut self.stack[self.idx];
+            for feature in threat_features(&acc.threats) {
+                acc_add(
+                    &mut acc.values[perspective],
+                    threat_weight,
+                    feature.idx(perspective, mirror),
+                );
+            }
+        }
+
         self.stack[self.idx].dirty[perspective] = false;
         self.stack[self.idx].needs_refresh[perspective] = false;
     }
@@ -184,15 +474,23 @@ impl Nnue {
             updates.remove_piece(to, victim, !stm);
         }
 
+        // The threats of the new position follow from applying the updates to the old one.
+        if self.net.threat_weight.is_some() {
+            let mut pieces = *board.piece_bbs();
+            let mut colors = *board.color_bbs();
+            for feature in updates.subs.iter().chain(&updates.adds) {
+                pieces[feature.piece] ^= feature.square;
+                colors[feature.color] ^= feature.square;
+            }
+            self.stack[self.idx + 1].threats = threats(&pieces, &colors);
+        }
+
         self.stack[self.idx].updates = updates;
         self.idx += 1;
         self.stack[self.idx].dirty = enum_map! { _ => true };
         self.stack[self.idx].needs_refresh = self.stack[self.idx - 1].needs_refresh;
 
-        if piece == Piece::King
-            && (king_bucket(from, stm) != king_bucket(to, stm)
-                || should_mirror(from) != should_mirror(to))
-        {
+        if piece == Piece::King && self.net.header.needs_refresh(from, to, stm) {
             self.stack[self.idx].needs_refresh[stm] = true;
         }
     }
@@ -202,16 +500,27 @@ impl Nnue {
     }
 
     pub fn update(&mut self, board: &Board) {
+        self.update_impl::<true>(board);
+    }
+
+    /// Like `update`, but applies the moves since the last clean accumulator one at a time,
+    /// writing every intermediate accumulator. Only meant for comparing against the fused
+    /// updates in `nnuebench`.
+    pub fn update_stepwise(&mut self, board: &Board) {
+        self.update_impl::<false>(board);
+    }
+
+    fn update_impl<const FUSED: bool>(&mut self, board: &Board) {
         for perspective in Color::all() {
             if self.stack[self.idx].needs_refresh[perspective] {
                 self.reset(board, perspective);
             } else if self.stack[self.idx].dirty[perspective] {
-                self.update_color(perspective, board);
+                self.update_color::<FUSED>(perspective, board);
             }
         }
     }
 
-    fn update_color(&mut self, perspective: Color, board: &Board) {
+    fn update_color<const FUSED: bool>(&mut self, perspective: Color, board: &Board) {
         let mut clean_idx = None;
 
         for i in (0..self.idx).rev() {
@@ -225,14 +534,64 @@ impl Nnue {
         }
 
         let king = board.king(perspective);
-        let bucket = king_bucket(king, perspective);
+        let mirror = self.net.header.should_mirror(king);
+        let bucket = self.net.header.king_bucket(king, perspective);
 
-        let Some(clean_idx) = clean_idx else {
+        let Some(mut clean_idx) = clean_idx else {
             self.reset(board, perspective);
             return;
         };
 
-        let weights = &NET.ft_weight[bucket];
+        let weights = self.net.ft_weights(bucket);
+
+        if FUSED && self.idx - clean_idx > 2 {
+            // Collect the net change over all moves up to the parent of the current position, so
+            // that only the parent has to be written instead of every accumulator in between. The
+            // parent is kept, since its other children are likely to be evaluated next. A feature
+            // that is added by one move and removed by a later one (or vice versa) cancels out, so
+            // each list only contains features of pieces that are in one of the two positions.
+            let parent = self.idx - 1;
+            let mut adds: ArrayVec<usize, 32> = ArrayVec::new();
+            let mut subs: ArrayVec<usize, 32> = ArrayVec::new();
+            for acc in &self.stack[clean_idx..parent] {
+                for sub in &acc.updates.subs {
+                    let sub = sub.idx(perspective, mirror);
+                    match adds.iter().position(|&add| add == sub) {
+                        Some(i) => _ = adds.swap_remove(i),
+                        None => subs.push(sub),
+                    }
+                }
+                for add in &acc.updates.adds {
+                    let add = add.idx(perspective, mirror);
+                    match subs.iter().position(|&sub| sub == add) {
+                        Some(i) => _ = subs.swap_remove(i),
+                        None => adds.push(add),
+                    }
+                }
+            }
+
+            let [clean, dirty] = self.stack.get_disjoint_mut([clean_idx, parent]).unwrap();
+            acc_fused(
+                &clean.values[perspective],
+                &mut dirty.values[perspective],
+                weights,
+                &adds,
+                &subs,
+            );
+
+            if let Some(threat_weight) = &self.net.threat_weight {
+                acc_threat_diff(
+                    &mut dirty.values[perspective],
+                    threat_weight,
+                    (&clean.threats, &dirty.threats),
+                    perspective,
+                    mirror,
+                );
+            }
+
+            dirty.dirty[perspective] = false;
+            clean_idx = parent;
+        }
 
         for idx in clean_idx..self.idx {
             let [clean, dirty] = self.stack.get_disjoint_mut([idx, idx + 1]).unwrap();
@@ -243,28 +602,39 @@ impl Nnue {
                     clean_acc,
                     dirty_acc,
                     weights,
-                    add.idx(perspective, king),
-                    sub.idx(perspective, king),
+                    add.idx(perspective, mirror),
+                    sub.idx(perspective, mirror),
                 ),
                 (&[add], &[sub1, sub2]) => acc_add_sub2(
                     clean_acc,
                     dirty_acc,
                     weights,
-                    add.idx(perspective, king),
-                    sub1.idx(perspective, king),
-                    sub2.idx(perspective, king),
+                    add.idx(perspective, mirror),
+                    sub1.idx(perspective, mirror),
+                    sub2.idx(perspective, mirror),
                 ),
                 (&[add1, add2], &[sub1, sub2]) => acc_add2_sub2(
                     clean_acc,
                     dirty_acc,
                     weights,
-                    add1.idx(perspective, king),
-                    add2.idx(perspective, king),
-                    sub1.idx(perspective, king),
-                    sub2.idx(perspective, king),
+                    add1.idx(perspective, mirror),
+                    add2.idx(perspective, mirror),
+                    sub1.idx(perspective, mirror),
+                    sub2.idx(perspective, mirror),
                 ),
                 _ => unreachable!("Invalid Updates"),
             };
+
+            if let Some(threat_weight) = &self.net.threat_weight {
+                acc_threat_diff(
+                    dirty_acc,
+                    threat_weight,
+                    (&clean.threats, &dirty.threats),
+                    perspective,
+                    mirror,
+                );
+            }
+
             dirty.dirty[perspective] = false;
         }
     }
@@ -273,6 +643,226 @@ impl Nnue {
         let acc = &self.stack[self.idx];
         let (us, them) = (&acc.values[stm], &acc.values[!stm]);
 
-        forward(us, them)
+        forward(self.net, us, them)
+    }
+
+    /// Win, draw and loss probabilities from the WDL head for the side to move `stm`, or `None`
+    /// if the net doesn't have one.
+    pub fn wdl(&self, stm: Color) -> Option<[f64; 3]> {
+        let acc = &self.stack[self.idx];
+        self.has_wdl()
+            .then(|| forward_wdl(self.net, &acc.values[stm], &acc.values[!stm]))
+    }
+
+    /// Like `wdl`, but for an arbitrary `board`. This refreshes both accumulators from scratch
+    /// and doesn't touch the accumulator stack.
+    pub fn wdl_of(&self, board: &Board) -> Option<[f64; 3]> {
+        if !self.has_wdl() {
+            return None;
+        }
+        let values = self.fresh_values(board, None);
+        let stm = board.stm();
+        Some(forward_wdl(self.net, &values[stm], &values[!stm]))
+    }
+
+    /// Evaluates `board` as if there was no piece on `removed`, by refreshing both accumulators
+    /// from scratch. This doesn't touch the accumulator stack, and is only meant for the eval
+    /// trace.
+    pub fn eval_without(&self, board: &Board, removed: Square) -> i32 {
+        let values = self.fresh_values(board, Some(removed));
+        let stm = board.stm();
+        forward(self.net, &values[stm], &values[!stm])
+    }
+
+    /// Computes both accumulators of `board` from scratch, leaving out the piece on `removed`.
+    fn fresh_values(&self, board: &Board, removed: Option<Square>) -> EnumMap<Color, Box<[i16]>> {
+        let threats = self.net.threat_weight.as_ref().map(|threat_weight| {
+            let mut pieces = *board.piece_bbs();
+            let mut colors = *board.color_bbs();
+            if let Some(removed) = removed {
+                for bb in pieces.values_mut().chain(colors.values_mut()) {
+                    if bb.contains(removed) {
+                        *bb ^= removed;
+                    }
+                }
+            }
+            (threat_weight, threats(&pieces, &colors))
+        });
+
+        enum_map! { perspective => {
+            let king = board.king(perspective);
+            let mirror = self.net.header.should_mirror(king);
+            let weights = self.net.ft_weights(self.net.header.king_bucket(king, perspective));
+            let mut values = Box::<[i16]>::from(&self.net.ft_bias[..]);
+
+            for color in Color::all() {
+                for piece in Piece::all() {
+                    for square in board.colored_pieces(piece, color) {
+                        if Some(square) == removed {
+                            continue;
+                        }
+                        let feature = Feature { piece, color, square };
+                        acc_add(&mut values, weights, feature.idx(perspective, mirror));
+                    }
+                }
+            }
+            if let Some((threat_weight, threats)) = &threats {
+                for feature in threat_features(threats) {
+                    acc_add(&mut values, threat_weight, feature.idx(perspective, mirror));
+                }
+            }
+            values
+        }}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use icarus_board::board::Board;
+    use icarus_common::{
+        piece::Color,
+        square::{File, Square},
+    };
+    use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+    use crate::{
+        bench::FENS,
+        nnue::network::{HEADER_SIZE, HL_ALIGN, INPUT, NET, NetHeader, Network, Nnue, Weights},
+    };
+
+    /// A net with small random weights for `header`. The embedded net has no threat inputs and
+    /// a single layout, so this is what covers the others.
+    fn random_net(header: NetHeader) -> &'static Network {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let threat_inputs = header.threat_inputs != 0;
+        let hl = HL_ALIGN;
+        let mut weights =
+            |n: usize| -> Weights { (0..n).map(|_| rng.random_range(-8..=8)).collect() };
+
+        Box::leak(Box::new(Network {
+            header,
+            hl,
+            ft_weight: weights(header.num_king_buckets as usize * INPUT * hl),
+            threat_weight: threat_inputs.then(|| weights(INPUT * hl)),
+            ft_bias: weights(hl),
+            out_weight: weights(2 * hl),
+            out_bias: 0,
+            wdl_weight: weights(0),
+            wdl_bias: [0; 3],
+        }))
+    }
+
+    fn check_incremental(net: &'static Network) {
+        // Updating only every few moves exercises the fused updates.
+        for interval in [1, 4] {
+            for fen in FENS {
+                let mut board = Board::read_fen(fen).unwrap();
+                let mut nnue = Nnue::with_net(net);
+                nnue.full_reset(&board);
+
+                for ply in 0..12 {
+                    let moves: Vec<_> = board.gen_all_moves_to();
+                    if moves.is_empty() {
+                        break;
+                    }
+                    let mv = moves[(ply * 7) % moves.len()];
+                    nnue.make_move(&board, mv);
+                    board.make_move(mv);
+                    if ply % interval != interval - 1 {
+                        continue;
+                    }
+                    nnue.update(&board);
+
+                    let mut fresh = Nnue::with_net(net);
+                    fresh.full_reset(&board);
+                    for perspective in Color::all() {
+                        assert_eq!(
+                            nnue.stack[nnue.idx].values[perspective],
+                            fresh.stack[0].values[perspective],
+                            "{fen} after {ply} plies"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn incremental_matches_refresh() {
+        check_incremental(*NET);
+    }
+
+    #[test]
+    fn incremental_matches_refresh_random_net() {
+        check_incremental(random_net(NetHeader::DEFAULT));
+        check_incremental(random_net(NetHeader {
+            threat_inputs: 1,
+            ..NetHeader::DEFAULT
+        }));
+    }
+
+    fn header(num_king_buckets: u8, mirror: u8, bucket: impl Fn(Square) -> u8) -> NetHeader {
+        NetHeader {
+            num_king_buckets,
+            mirror,
+            king_bucket_layout: std::array::from_fn(|sq| bucket(Square::from_idx(sq as u8))),
+            ..NetHeader::DEFAULT
+        }
+    }
+
+    #[test]
+    fn loads_any_king_buckets() {
+        let headers = [
+            // Two ranks per bucket.
+            header(4, 1, |sq| sq.rank().idx() / 2),
+            // A bucket per square of the mirrored half.
+            header(32, 1, |sq| {
+                sq.rank().idx() * 4 + sq.file().idx().min(7 - sq.file().idx())
+            }),
+            // Kings on the E to H files get their own buckets instead of being mirrored.
+            header(8, 0, |sq| {
+                sq.rank().idx() / 2 * 2 + (sq.file() > File::D) as u8
+            }),
+        ];
+
+        for header in headers {
+            let net = random_net(header);
+            let loaded = Box::leak(Network::from_bytes(net.to_bytes()).unwrap());
+
+            assert_eq!(loaded.header.to_bytes(), header.to_bytes());
+            assert_eq!(loaded.hl, net.hl);
+            assert_eq!(loaded.ft_weight[..], net.ft_weight[..]);
+            assert_eq!(loaded.out_weight[..], net.out_weight[..]);
+
+            for sq in Square::all() {
+                let layout = header.king_bucket_layout;
+                assert_eq!(
+                    loaded.header.king_bucket(sq, Color::White),
+                    layout[sq] as usize
+                );
+                assert_eq!(
+                    loaded.header.king_bucket(sq, Color::Black),
+                    layout[sq.flip_rank()] as usize
+                );
+                assert_eq!(
+                    loaded.header.should_mirror(sq),
+                    header.mirror != 0 && sq.file() > File::D
+                );
+            }
+
+            check_incremental(loaded);
+        }
+    }
+
+    #[test]
+    fn rejects_out_of_range_king_buckets() {
+        let mut header = header(4, 1, |sq| sq.rank().idx() / 2);
+        let mut bytes = random_net(header).to_bytes();
+        assert!(Network::from_bytes(bytes.clone()).is_ok());
+
+        header.king_bucket_layout[Square::H8] = 4;
+        assert!(header.validate().is_err());
+        bytes[..HEADER_SIZE].copy_from_slice(&header.to_bytes());
+        assert!(Network::from_bytes(bytes).is_err());
     }
 }
@@ -0,0 +1,150 @@
+//! The `nettransform` command, which rewrites a network into a smaller or faster equivalent one.
+//! Hidden neurons that never activate over a sample of positions can be pruned, and the
+//! remaining ones can be sorted by how often they activate. The hidden layer size is taken from
+//! the net file, so the result can be loaded like any other net.
+
+use std::{cmp::Reverse, fs, iter, path::Path};
+
+use icarus_board::board::Board;
+use icarus_common::piece::{Color, Piece};
+
+use crate::nnue::{
+    accumulator::{Feature, threat_features, threats},
+    compress,
+    inference::{QA, QB, SCALE},
+    netinfo::{ActivationStats, load_inputs},
+    network::{HL_ALIGN, INPUT, Network, Weights},
+};
+
+/// Builds a net from the hidden neurons of `net` listed in `neurons`, in that order. The hidden
+/// layer is padded with neurons that are always zero, up to a multiple of `HL_ALIGN`.
+fn select(net: &Network, neurons: &[usize]) -> Network {
+    let hl = neurons.len().next_multiple_of(HL_ALIGN).max(HL_ALIGN);
+    let pick = |values: &[i16]| -> Weights {
+        values
+            .chunks(net.hl)
+            .flat_map(|row| {
+                let padding = iter::repeat_n(0, hl - neurons.len());
+                neurons.iter().map(|&i| row[i]).chain(padding)
+            })
+            .collect()
+    };
+
+    Network {
+        header: net.header,
+        hl,
+        ft_weight: pick(&net.ft_weight),
+        threat_weight: net.threat_weight.as_deref().map(pick),
+        ft_bias: pick(&net.ft_bias),
+        out_weight: pick(&net.out_weight),
+        out_bias: net.out_bias,
+        wdl_weight: pick(&net.wdl_weight),
+        wdl_bias: net.wdl_bias,
+    }
+}
+
+fn accumulator(net: &Network, board: &Board, perspective: Color) -> Vec<i16> {
+    let king = board.king(perspective);
+    let mirror = net.header.should_mirror(king);
+    let weights = net.ft_weights(net.header.king_bucket(king, perspective));
+    let mut acc = net.ft_bias.to_vec();
+
+    for color in Color::all() {
+        for piece in Piece::all() {
+            for square in board.colored_pieces(piece, color) {
+                let feature = Feature {
+                    piece,
+                    color,
+                    square,
+                };
+                let row = &weights[feature.idx(perspective, mirror) * net.hl..][..net.hl];
+                for (acc, &w) in acc.iter_mut().zip(row) {
+                    *acc = acc.wrapping_add(w);
+                }
+            }
+        }
+    }
+
+    if let Some(threat_weight) = &net.threat_weight {
+        for feature in threat_features(&threats(board.piece_bbs(), board.color_bbs())) {
+            let row = &threat_weight[feature.idx(perspective, mirror) * net.hl..][..net.hl];
+            for (acc, &w) in acc.iter_mut().zip(row) {
+                *acc = acc.wrapping_add(w);
+            }
+        }
+    }
+
+    acc
+}
+
+/// Evaluates `board` exactly like the generic `forward`, but with wrapping arithmetic, so that
+/// overflows show up as mismatches instead of panics.
+fn eval(net: &Network, board: &Board) -> i32 {
+    let stm = board.stm();
+    let mut output = 0i32;
+    for (side, perspective) in [stm, !stm].into_iter().enumerate() {
+        let acc = accumulator(net, board, perspective);
+        for (&value, &weight) in acc.iter().zip(net.out_weights(side)) {
+            let clamped = value.clamp(0, QA);
+            output += i32::from(clamped.wrapping_mul(weight)) * i32::from(clamped);
+        }
+    }
+
+    output /= i32::from(QA);
+    output += i32::from(net.out_bias);
+    output *= SCALE;
+    output / (i32::from(QA) * i32::from(QB))
+}
+
+pub fn nettransform(
+    net_path: Option<&Path>,
+    positions: Option<&Path>,
+    out: &Path,
+    prune: bool,
+    permute: bool,
+    compress: bool,
+) {
+    let Some((net, boards)) = load_inputs(net_path, positions) else {
+        return;
+    };
+
+    let stats = ActivationStats::collect(&net, &boards);
+    let mut neurons: Vec<usize> = (0..net.hl).collect();
+    if permute {
+        neurons.sort_by_key(|&i| Reverse(stats.active[i]));
+    }
+    if prune {
+        neurons.retain(|&i| stats.active[i] > 0);
+    }
+
+    let transformed = select(&net, &neurons);
+
+    // Pruned neurons were never active on the sample, so the evals have to match there.
+    let mismatches = boards
+        .iter()
+        .filter(|board| eval(&net, board) != eval(&transformed, board))
+        .count();
+    if mismatches != 0 {
+        println!("info string Transformed net differs on {mismatches} positions, not writing it");
+        return;
+    }
+
+    let mut bytes = transformed.to_bytes();
+    if compress {
+        bytes = compress::compress(&bytes);
+    }
+    if let Err(e) = fs::write(out, bytes) {
+        println!("info string Failed to write `{}`: {e}", out.display());
+        return;
+    }
+
+    println!(
+        "Kept {} of {} neurons, hidden layer size {} ({} king buckets, {INPUT} inputs)",
+        neurons.len(),
+        net.hl,
+        transformed.hl,
+        net.header.num_king_buckets,
+    );
+    println!("Verified identical evals on {} positions", boards.len());
+    println!("Wrote `{}`", out.display());
+}
@@ -1,4 +1,6 @@
-// TEMPORARY eval using pesto tables. Not here to stay.
+// Classical eval using PeSTO style tapered piece-square tables. The tables and phase weights can
+// be refitted to datagen output with the `texel` subcommand, which prints them in the format used
+// below.
 
 use icarus_board::board::Board;
 use icarus_common::{
@@ -9,7 +11,7 @@ use icarus_common::{
 
 use crate::score::Score;
 
-static PHASE_INC: [u8; 6] = [0, 1, 1, 2, 4, 0];
+pub static PHASE_INC: [u8; 6] = [0, 1, 1, 2, 4, 0];
 
 pub fn eval(board: &Board) -> Score {
     let mut mg = [0, 0];
@@ -67,7 +69,7 @@ pub fn _eval(board: &Board) -> Score {
 
 //indexed by [phase][piece][color][square]
 #[rustfmt::skip]
-static PESTO: [[[[i16; 64]; 2]; 6]; 2] = [
+pub static PESTO: [[[[i16; 64]; 2]; 6]; 2] = [
     [
         [
             [
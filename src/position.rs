@@ -1,7 +1,8 @@
 use icarus_board::{
     attack_generators::{bishop_moves, rook_moves},
-    board::{Board, TerminalState},
+    board::{Board, TerminalState, Undo},
     r#move::{Move, MoveFlag},
+    zobrist::ZOBRIST,
 };
 use icarus_common::{
     lookups::{king_moves, knight_moves, pawn_attacks},
@@ -9,18 +10,23 @@ use icarus_common::{
     square::Square,
 };
 
-use crate::{
-    nnue::network::Nnue,
-    score::Score,
-    search::params::{mat_scale, mat_scaling_base, see_val},
-};
+use crate::{nnue::network::Nnue, score::Score, search::params::Params};
+
+/// A previously played move, along with everything needed to take it back.
+#[derive(Clone, Copy)]
+struct HistoryEntry {
+    /// Hash of the board before the move was made.
+    hash: u64,
+    /// The moved piece and the move, or `None` for null moves.
+    mv: Option<(Piece, Move)>,
+    undo: Undo,
+}
 
 #[derive(Clone)]
 pub struct Position {
     board: Board,
-    /// Previously played boards. `history[0]` is the starting position.
-    history: Vec<Board>,
-    moves: Vec<Option<(Piece, Move)>>,
+    /// Previously played moves. `history[0]` is the first move made from the starting position.
+    history: Vec<HistoryEntry>,
 }
 
 impl Position {
@@ -28,7 +34,6 @@ impl Position {
         Self {
             board,
             history: vec![],
-            moves: vec![],
         }
     }
 
@@ -37,39 +42,46 @@ impl Position {
         if let Some(nnue) = nnue {
             nnue.make_move(&self.board, mv);
         }
-        self.history.push(self.board);
-        self.board.make_move(mv);
-        self.moves.push(Some((piece, mv)));
+        let hash = self.board.hash();
+        let undo = self.board.make_move(mv);
+        self.history.push(HistoryEntry {
+            hash,
+            mv: Some((piece, mv)),
+            undo,
+        });
     }
 
     pub fn make_null_move(&mut self) {
-        self.history.push(self.board);
-        self.board.make_null_move();
-        self.moves.push(None);
+        let hash = self.board.hash();
+        let undo = self.board.make_null_move();
+        self.history.push(HistoryEntry {
+            hash,
+            mv: None,
+            undo,
+        });
     }
 
     pub fn unmake_move(&mut self, nnue: Option<&mut Nnue>) {
         if let Some(nnue) = nnue {
             nnue.unmake_move();
         }
-        self.board = self.history.pop().unwrap();
-        self.moves.pop();
+        let entry = self.history.pop().unwrap();
+        let (_, mv) = entry.mv.expect("Unmade a null move with `unmake_move`");
+        self.board.unmake_move(mv, &entry.undo);
     }
 
     pub fn unmake_null_move(&mut self) {
-        self.board = self.history.pop().unwrap();
-        self.moves.pop();
+        let entry = self.history.pop().unwrap();
+        debug_assert!(entry.mv.is_none());
+        self.board.unmake_null_move(&entry.undo);
     }
 
-    pub fn eval(&self, nnue: &mut Nnue, mat_scaling: bool) -> Score {
+    pub fn eval(&self, nnue: &mut Nnue, mat_scaling: bool, params: &Params) -> Score {
         nnue.update(&self.board);
         let eval = nnue.eval(self.board.stm());
 
         let scale = if mat_scaling {
-            mat_scaling_base()
-                + Piece::all()
-                    .map(|pt| self.board.pieces(pt).popcnt() as i32 * mat_scale(pt))
-                    .sum::<i32>()
+            self.material_scale(params)
         } else {
             32768
         };
@@ -77,11 +89,25 @@ impl Position {
         Score::clamp_nomate((eval * scale / 32768).clamp(i16::MIN as i32, i16::MAX as i32) as i16)
     }
 
+    /// The factor the NNUE output is multiplied with when material scaling is enabled, in units
+    /// of 1/32768.
+    pub fn material_scale(&self, params: &Params) -> i32 {
+        params.mat_scaling_base
+            + Piece::all()
+                .map(|pt| self.board.pieces(pt).popcnt() as i32 * params.mat_scale(pt))
+                .sum::<i32>()
+    }
+
     pub fn prev_move(&self, ply: usize) -> Option<(Piece, Move)> {
-        self.moves
+        self.history
             .len()
             .checked_sub(ply)
-            .and_then(|i| self.moves[i])
+            .and_then(|i| self.history[i].mv)
+    }
+
+    /// Returns the moves played so far, excluding null moves.
+    pub fn moves(&self) -> impl Iterator<Item = Move> + '_ {
+        self.history.iter().filter_map(|e| e.mv.map(|(_, mv)| mv))
     }
 
     pub fn board(&self) -> &Board {
@@ -96,14 +122,37 @@ impl Position {
             .skip(3)
             .take((self.board.halfmove_clock() as usize).saturating_sub(3))
             .step_by(2)
-            .any(|b| b.hash() == self.board.hash())
+            .any(|e| e.hash == self.board.hash())
+    }
+
+    /// Returns whether the side to move has a reversible move that repeats an earlier position.
+    pub fn upcoming_repetition(&self) -> bool {
+        let end = (self.board.halfmove_clock() as usize).min(self.history.len());
+        if end < 3 {
+            return false;
+        }
+
+        // Hash of the board `i` plies ago.
+        let hash = |i: usize| self.history[self.history.len() - i].hash;
+
+        // Accumulates the hash differences of the opponent's moves. Only if they cancel out
+        // can a single move of ours lead back to the board `i` plies ago.
+        let mut other = self.board.hash() ^ hash(1) ^ ZOBRIST.black_to_move;
+        for i in (3..=end).step_by(2) {
+            other ^= hash(i - 1) ^ hash(i) ^ ZOBRIST.black_to_move;
+            if other == 0 && self.board.has_reversible_move_to(hash(i)) {
+                return true;
+            }
+        }
+
+        false
     }
 
     pub fn is_draw(&self) -> bool {
         self.board.terminal_state() == Some(TerminalState::Draw) || self.repetition()
     }
 
-    pub fn cmp_see(&self, mv: Move, threshold: i32) -> bool {
+    pub fn cmp_see(&self, mv: Move, threshold: i32, params: &Params) -> bool {
         // Heavily inspired by <https://github.com/AndyGrant/Ethereal/blob/0e47e9b67f345c75eb965d9fb3e2493b6a11d09a/src/search.c>
 
         let board = &self.board;
@@ -119,14 +168,16 @@ impl Position {
             .unwrap();
 
         let mut balance = -threshold
-            + mv.captures(board).map_or(0, see_val)
-            + mv.promotes_to()
-                .map_or(0, |promo| see_val(promo) - see_val(Piece::Pawn));
+            + mv.captures(board)
+                .map_or(0, |victim| params.see_val(victim))
+            + mv.promotes_to().map_or(0, |promo| {
+                params.see_val(promo) - params.see_val(Piece::Pawn)
+            });
         if balance < 0 {
             return false;
         }
 
-        balance -= see_val(next_victim);
+        balance -= params.see_val(next_victim);
 
         if balance >= 0 {
             return true;
@@ -175,7 +226,7 @@ impl Position {
             attackers &= occupied;
             stm = !stm;
 
-            balance = -balance - 1 - see_val(next_victim);
+            balance = -balance - 1 - params.see_val(next_victim);
 
             if balance >= 0 {
                 if next_victim == Piece::King && (attackers & board.occupied_by(stm)).is_non_empty()
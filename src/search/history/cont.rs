@@ -1,15 +1,7 @@
 use icarus_board::{board::Board, r#move::Move};
 use icarus_common::piece::Piece;
 
-use crate::search::{
-    history::MAX_HIST_VALUE,
-    params::{
-        cont1_bonus_base, cont1_bonus_max, cont1_bonus_scale, cont1_malus_base, cont1_malus_max,
-        cont1_malus_scale, cont2_bonus_base, cont2_bonus_max, cont2_bonus_scale, cont2_malus_base,
-        cont2_malus_max, cont2_malus_scale, cont4_bonus_base, cont4_bonus_max, cont4_bonus_scale,
-        cont4_malus_base, cont4_malus_max, cont4_malus_scale,
-    },
-};
+use crate::search::{history::MAX_HIST_VALUE, params::Params};
 
 pub struct ContHist {
     /// [stm][prev piece][prev dst][piece][dst]
@@ -29,21 +21,45 @@ fn apply_gravity<const MAX_BONUS: i32, const MAX_VALUE: i32>(
 }
 
 impl ContHist {
-    fn bonus<const PLY: usize>(depth: i16) -> i32 {
+    fn bonus<const PLY: usize>(depth: i16, params: &Params) -> i32 {
         let (bonus_base, bonus_scale, bonus_max) = match PLY {
-            1 => (cont1_bonus_base(), cont1_bonus_scale(), cont1_bonus_max()),
-            2 => (cont2_bonus_base(), cont2_bonus_scale(), cont2_bonus_max()),
-            4 => (cont4_bonus_base(), cont4_bonus_scale(), cont4_bonus_max()),
+            1 => (
+                params.cont1_bonus_base,
+                params.cont1_bonus_scale,
+                params.cont1_bonus_max,
+            ),
+            2 => (
+                params.cont2_bonus_base,
+                params.cont2_bonus_scale,
+                params.cont2_bonus_max,
+            ),
+            4 => (
+                params.cont4_bonus_base,
+                params.cont4_bonus_scale,
+                params.cont4_bonus_max,
+            ),
             _ => unreachable!(),
         };
         (bonus_base + (depth as i32) * bonus_scale).min(bonus_max)
     }
 
-    fn malus<const PLY: usize>(depth: i16) -> i32 {
+    fn malus<const PLY: usize>(depth: i16, params: &Params) -> i32 {
         let (malus_base, malus_scale, malus_max) = match PLY {
-            1 => (cont1_malus_base(), cont1_malus_scale(), cont1_malus_max()),
-            2 => (cont2_malus_base(), cont2_malus_scale(), cont2_malus_max()),
-            4 => (cont4_malus_base(), cont4_malus_scale(), cont4_malus_max()),
+            1 => (
+                params.cont1_malus_base,
+                params.cont1_malus_scale,
+                params.cont1_malus_max,
+            ),
+            2 => (
+                params.cont2_malus_base,
+                params.cont2_malus_scale,
+                params.cont2_malus_max,
+            ),
+            4 => (
+                params.cont4_malus_base,
+                params.cont4_malus_scale,
+                params.cont4_malus_max,
+            ),
             _ => unreachable!(),
         };
         (malus_base + (depth as i32) * malus_scale).min(malus_max)
@@ -77,12 +93,13 @@ impl ContHist {
         prev: Option<(Piece, Move)>,
         total: i32,
         depth: i16,
+        params: &Params,
     ) {
         if let Some(entry) = self.get_mut(board, mv, prev) {
             apply_gravity::<MAX_HIST_VALUE, MAX_HIST_VALUE>(
                 entry,
                 total,
-                Self::bonus::<PLY>(depth),
+                Self::bonus::<PLY>(depth, params),
             );
         }
     }
@@ -94,12 +111,13 @@ impl ContHist {
         prev: Option<(Piece, Move)>,
         total: i32,
         depth: i16,
+        params: &Params,
     ) {
         if let Some(entry) = self.get_mut(board, mv, prev) {
             apply_gravity::<MAX_HIST_VALUE, MAX_HIST_VALUE>(
                 entry,
                 total,
-                -Self::malus::<PLY>(depth),
+                -Self::malus::<PLY>(depth, params),
             );
         }
     }
@@ -2,7 +2,7 @@ use icarus_common::piece::Color;
 
 use crate::search::{
     history::{CORR_SIZE, MAX_CORR_VALUE, apply_gravity},
-    params::{corr_bonus_div, corr_bonus_scale},
+    params::Params,
 };
 
 pub struct CorrHist {
@@ -11,8 +11,8 @@ pub struct CorrHist {
 }
 
 impl CorrHist {
-    pub fn amount(delta: i32, depth: i16) -> i32 {
-        (delta * (depth as i32) * corr_bonus_scale()) / corr_bonus_div()
+    pub fn amount(delta: i32, depth: i16, params: &Params) -> i32 {
+        (delta * (depth as i32) * params.corr_bonus_scale) / params.corr_bonus_div
     }
 
     pub fn get(&self, stm: Color, hash: u64) -> i16 {
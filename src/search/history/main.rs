@@ -2,10 +2,7 @@ use icarus_board::{board::Board, r#move::Move};
 
 use crate::search::{
     history::{MAX_HIST_VALUE, apply_gravity},
-    params::{
-        main_bonus_base, main_bonus_max, main_bonus_scale, main_malus_base, main_malus_max,
-        main_malus_scale,
-    },
+    params::Params,
 };
 
 pub struct MainHist {
@@ -14,12 +11,14 @@ pub struct MainHist {
 }
 
 impl MainHist {
-    fn bonus(depth: i16) -> i32 {
-        (main_bonus_base() + (depth as i32) * main_bonus_scale()).min(main_bonus_max())
+    fn bonus(depth: i16, params: &Params) -> i32 {
+        (params.main_bonus_base + (depth as i32) * params.main_bonus_scale)
+            .min(params.main_bonus_max)
     }
 
-    fn malus(depth: i16) -> i32 {
-        (main_malus_base() + (depth as i32) * main_malus_scale()).min(main_malus_max())
+    fn malus(depth: i16, params: &Params) -> i32 {
+        (params.main_malus_base + (depth as i32) * params.main_malus_scale)
+            .min(params.main_malus_max)
     }
 
     pub fn get(&self, board: &Board, mv: Move) -> i16 {
@@ -38,17 +37,17 @@ impl MainHist {
         &mut self.data[stm][from][from_threatened][to][to_threatened]
     }
 
-    pub fn apply_bonus(&mut self, board: &Board, mv: Move, depth: i16) {
+    pub fn apply_bonus(&mut self, board: &Board, mv: Move, depth: i16, params: &Params) {
         apply_gravity::<MAX_HIST_VALUE, MAX_HIST_VALUE>(
             self.get_mut(board, mv),
-            Self::bonus(depth),
+            Self::bonus(depth, params),
         );
     }
 
-    pub fn apply_malus(&mut self, board: &Board, mv: Move, depth: i16) {
+    pub fn apply_malus(&mut self, board: &Board, mv: Move, depth: i16, params: &Params) {
         apply_gravity::<MAX_HIST_VALUE, MAX_HIST_VALUE>(
             self.get_mut(board, mv),
-            -Self::malus(depth),
+            -Self::malus(depth, params),
         );
     }
 }
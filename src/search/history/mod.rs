@@ -15,10 +15,7 @@ use crate::{
             cont::ContHist, contcorr::ContCorrHist, corr::CorrHist, main::MainHist,
             tactic::TacticHist,
         },
-        params::{
-            corr_black_factor, corr_cont1_factor, corr_cont2_factor, corr_major_factor,
-            corr_minor_factor, corr_pawn_factor, corr_white_factor,
-        },
+        params::Params,
     },
 };
 
@@ -72,26 +69,26 @@ impl History {
         self.tactic.get(board, mv) as i32
     }
 
-    pub fn corr(&self, pos: &Position) -> i16 {
+    pub fn corr(&self, pos: &Position, params: &Params) -> i16 {
         let board = pos.board();
         let stm = board.stm();
         let (twoply, oneply, cur) = (pos.prev_move(3), pos.prev_move(2), pos.prev_move(1));
 
         let mut corr = 0;
-        corr += (self.pawn_corr.get(stm, board.pawn_hash()) as i32) * corr_pawn_factor();
-        corr += (self.minor_corr.get(stm, board.minor_hash()) as i32) * corr_minor_factor();
-        corr += (self.major_corr.get(stm, board.major_hash()) as i32) * corr_major_factor();
+        corr += (self.pawn_corr.get(stm, board.pawn_hash()) as i32) * params.corr_pawn_factor;
+        corr += (self.minor_corr.get(stm, board.minor_hash()) as i32) * params.corr_minor_factor;
+        corr += (self.major_corr.get(stm, board.major_hash()) as i32) * params.corr_major_factor;
         corr += (self
             .white_nonpawn_corr
             .get(stm, board.nonpawn_hash(Color::White)) as i32)
-            * corr_white_factor();
+            * params.corr_white_factor;
         corr += (self
             .black_nonpawn_corr
             .get(stm, board.nonpawn_hash(Color::Black)) as i32)
-            * corr_black_factor();
+            * params.corr_black_factor;
 
-        corr += self.contcorr_oneply.get(stm, cur, oneply) as i32 * corr_cont1_factor();
-        corr += self.contcorr_twoply.get(stm, cur, twoply) as i32 * corr_cont2_factor();
+        corr += self.contcorr_oneply.get(stm, cur, oneply) as i32 * params.corr_cont1_factor;
+        corr += self.contcorr_twoply.get(stm, cur, twoply) as i32 * params.corr_cont2_factor;
 
         (corr / MAX_CORR_VALUE) as i16
     }
@@ -103,6 +100,7 @@ impl History {
         quiets: &[Move],
         tactics: &[Move],
         depth: i16,
+        params: &Params,
     ) {
         let board = pos.board();
         let oneply = pos.prev_move(1);
@@ -111,40 +109,47 @@ impl History {
         let cont_score = self.cont(pos, mv);
 
         if board.is_tactic(mv) {
-            self.tactic.apply_bonus(board, mv, depth);
+            self.tactic.apply_bonus(board, mv, depth, params);
         } else {
-            self.main.apply_bonus(board, mv, depth);
+            self.main.apply_bonus(board, mv, depth, params);
             self.cont_odd
-                .apply_bonus::<1>(board, mv, oneply, cont_score, depth);
+                .apply_bonus::<1>(board, mv, oneply, cont_score, depth, params);
             self.cont_even
-                .apply_bonus::<2>(board, mv, twoply, cont_score, depth);
+                .apply_bonus::<2>(board, mv, twoply, cont_score, depth, params);
             self.cont_even
-                .apply_bonus::<4>(board, mv, fourply, cont_score, depth);
+                .apply_bonus::<4>(board, mv, fourply, cont_score, depth, params);
 
             for &quiet in quiets {
-                self.main.apply_malus(board, quiet, depth);
+                self.main.apply_malus(board, quiet, depth, params);
                 self.cont_odd
-                    .apply_malus::<1>(board, quiet, oneply, cont_score, depth);
+                    .apply_malus::<1>(board, quiet, oneply, cont_score, depth, params);
                 self.cont_even
-                    .apply_malus::<2>(board, quiet, twoply, cont_score, depth);
+                    .apply_malus::<2>(board, quiet, twoply, cont_score, depth, params);
                 self.cont_even
-                    .apply_malus::<4>(board, quiet, fourply, cont_score, depth);
+                    .apply_malus::<4>(board, quiet, fourply, cont_score, depth, params);
             }
         }
 
         for &tactic in tactics {
-            self.tactic.apply_malus(board, tactic, depth);
+            self.tactic.apply_malus(board, tactic, depth, params);
         }
     }
 
-    pub fn update_corr(&mut self, pos: &Position, depth: i16, score: Score, static_eval: Score) {
+    pub fn update_corr(
+        &mut self,
+        pos: &Position,
+        depth: i16,
+        score: Score,
+        static_eval: Score,
+        params: &Params,
+    ) {
         let board = pos.board();
         let stm = board.stm();
         let (twoply, oneply, cur) = (pos.prev_move(3), pos.prev_move(2), pos.prev_move(1));
 
         let delta = score.0 as i32 - static_eval.0 as i32;
 
-        let amount = CorrHist::amount(delta, depth);
+        let amount = CorrHist::amount(delta, depth, params);
 
         self.pawn_corr.update(stm, board.pawn_hash(), amount);
         self.minor_corr.update(stm, board.minor_hash(), amount);
@@ -2,10 +2,7 @@ use icarus_board::{board::Board, r#move::Move};
 
 use crate::search::{
     history::{MAX_HIST_VALUE, apply_gravity},
-    params::{
-        tactic_bonus_base, tactic_bonus_max, tactic_bonus_scale, tactic_malus_base,
-        tactic_malus_max, tactic_malus_scale,
-    },
+    params::Params,
 };
 
 pub struct TacticHist {
@@ -14,12 +11,14 @@ pub struct TacticHist {
 }
 
 impl TacticHist {
-    fn bonus(depth: i16) -> i32 {
-        (tactic_bonus_base() + (depth as i32) * tactic_bonus_scale()).min(tactic_bonus_max())
+    fn bonus(depth: i16, params: &Params) -> i32 {
+        (params.tactic_bonus_base + (depth as i32) * params.tactic_bonus_scale)
+            .min(params.tactic_bonus_max)
     }
 
-    fn malus(depth: i16) -> i32 {
-        (tactic_malus_base() + (depth as i32) * tactic_malus_scale()).min(tactic_malus_max())
+    fn malus(depth: i16, params: &Params) -> i32 {
+        (params.tactic_malus_base + (depth as i32) * params.tactic_malus_scale)
+            .min(params.tactic_malus_max)
     }
 
     pub fn get(&self, board: &Board, mv: Move) -> i16 {
@@ -36,17 +35,17 @@ impl TacticHist {
         &mut self.data[stm][piece][to]
     }
 
-    pub fn apply_bonus(&mut self, board: &Board, mv: Move, depth: i16) {
+    pub fn apply_bonus(&mut self, board: &Board, mv: Move, depth: i16, params: &Params) {
         apply_gravity::<MAX_HIST_VALUE, MAX_HIST_VALUE>(
             self.get_mut(board, mv),
-            Self::bonus(depth),
+            Self::bonus(depth, params),
         );
     }
 
-    pub fn apply_malus(&mut self, board: &Board, mv: Move, depth: i16) {
+    pub fn apply_malus(&mut self, board: &Board, mv: Move, depth: i16, params: &Params) {
         apply_gravity::<MAX_HIST_VALUE, MAX_HIST_VALUE>(
             self.get_mut(board, mv),
-            -Self::malus(depth),
+            -Self::malus(depth, params),
         );
     }
 }
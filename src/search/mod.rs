@@ -5,5 +5,8 @@ pub mod move_picker;
 pub mod params;
 pub mod search;
 pub mod searcher;
+pub mod skill;
+pub mod stats;
 pub mod time_manager;
+pub mod trace;
 pub mod transposition_table;
@@ -2,7 +2,7 @@ use arrayvec::ArrayVec;
 use icarus_board::{r#move::Move, movegen::Abort};
 use icarus_common::piece::Piece;
 
-use crate::{position::Position, search::params::see_val, search::searcher::ThreadCtx};
+use crate::{position::Position, search::searcher::ThreadCtx};
 
 #[derive(Clone, Copy, Debug)]
 pub struct ScoredMove(pub Move, pub i32);
@@ -95,10 +95,12 @@ impl MovePicker {
                 Abort::No
             });
             for mv in &mut self.moves {
-                let victim = mv.0.captures(board).map_or(0, see_val);
+                let victim =
+                    mv.0.captures(board)
+                        .map_or(0, |victim| thread.params.see_val(victim));
                 mv.1 = thread.history.score_tactic(board, mv.0) / 8 + victim * 8;
                 if let Some(promo) = mv.0.promotes_to() {
-                    mv.1 += (see_val(promo) - see_val(Piece::Pawn)) * 8;
+                    mv.1 += (thread.params.see_val(promo) - thread.params.see_val(Piece::Pawn)) * 8;
                 }
             }
 
@@ -115,7 +117,7 @@ impl MovePicker {
             self.moves.swap(self.index, i);
             self.index += 1;
 
-            if pos.cmp_see(mv, self.see_threshold) {
+            if pos.cmp_see(mv, self.see_threshold, &thread.params) {
                 return Some(mv);
             }
 
@@ -1,3 +1,5 @@
+use std::{fs, path::Path};
+
 use icarus_common::piece::Piece;
 
 use crate::{nontunable, search::search::DEPTH_SCALE, tunable_params, util::MAX_PLY};
@@ -129,28 +131,6 @@ nontunable!(
     asp_min_depth           : u16 = 5       (2..=10);
 );
 
-pub fn see_val(piece: Piece) -> i32 {
-    match piece {
-        Piece::Pawn => pawn_see_val(),
-        Piece::Knight => knight_see_val(),
-        Piece::Bishop => bishop_see_val(),
-        Piece::Rook => rook_see_val(),
-        Piece::Queen => queen_see_val(),
-        Piece::King => 0,
-    }
-}
-
-pub fn mat_scale(piece: Piece) -> i32 {
-    match piece {
-        Piece::Pawn => pawn_mat_scale(),
-        Piece::Knight => knight_mat_scale(),
-        Piece::Bishop => bishop_mat_scale(),
-        Piece::Rook => rook_mat_scale(),
-        Piece::Queen => queen_mat_scale(),
-        Piece::King => 0,
-    }
-}
-
 // Precomputed log table because ln() is probably slow and also not a `const fn` :(
 #[allow(clippy::approx_constant)]
 static LOG: [f32; MAX_PLY as usize] = [
@@ -185,12 +165,111 @@ static LOG: [f32; MAX_PLY as usize] = [
     5.5134287, 5.5174527, 5.521461, 5.525453, 5.529429, 5.5333896, 5.5373344, 5.5412636,
 ];
 
-pub fn get_lmr(is_tactic: bool, depth: u8, moves_seen: u8) -> i32 {
-    let base = lmr_base() as f32 / 1024.0;
-    let div = if is_tactic {
-        1024.0 / (lmr_tactic_div() as f32)
-    } else {
-        1024.0 / (lmr_quiet_div() as f32)
-    };
-    (base + LOG[depth as usize] * LOG[moves_seen as usize] * div) as i32 * DEPTH_SCALE
+impl Params {
+    pub fn see_val(&self, piece: Piece) -> i32 {
+        match piece {
+            Piece::Pawn => self.pawn_see_val,
+            Piece::Knight => self.knight_see_val,
+            Piece::Bishop => self.bishop_see_val,
+            Piece::Rook => self.rook_see_val,
+            Piece::Queen => self.queen_see_val,
+            Piece::King => 0,
+        }
+    }
+
+    pub fn mat_scale(&self, piece: Piece) -> i32 {
+        match piece {
+            Piece::Pawn => self.pawn_mat_scale,
+            Piece::Knight => self.knight_mat_scale,
+            Piece::Bishop => self.bishop_mat_scale,
+            Piece::Rook => self.rook_mat_scale,
+            Piece::Queen => self.queen_mat_scale,
+            Piece::King => 0,
+        }
+    }
+
+    pub fn get_lmr(&self, is_tactic: bool, depth: u8, moves_seen: u8) -> i32 {
+        let base = self.lmr_base as f32 / 1024.0;
+        let div = if is_tactic {
+            1024.0 / (self.lmr_tactic_div as f32)
+        } else {
+            1024.0 / (self.lmr_quiet_div as f32)
+        };
+        (base + LOG[depth as usize] * LOG[moves_seen as usize] * div) as i32 * DEPTH_SCALE
+    }
+
+    /// Parses a parameter set from `name value` lines, with `#` starting a comment. Parameters
+    /// that aren't listed keep their defaults, so SPSA checkpoints can be loaded directly. The
+    /// whole set is rejected if any name is unknown or any value is out of range.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut params = Self::DEFAULT;
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid = |msg: &str| format!("Line {}: {msg} `{line}`", i + 1);
+            let mut tokens = line.split_whitespace();
+            let (Some(name), Some(value), None) = (tokens.next(), tokens.next(), tokens.next())
+            else {
+                return Err(invalid("Expected `name value`, got"));
+            };
+            let value: f64 = value.parse().map_err(|_| invalid("Invalid value in"))?;
+
+            let Some(spec) = Self::SPECS.iter().find(|spec| spec.name == name) else {
+                return Err(invalid("Unknown parameter in"));
+            };
+            if !params.set(name, value) {
+                return Err(invalid(&format!(
+                    "Value out of range {}..={} in",
+                    spec.min, spec.max
+                )));
+            }
+        }
+
+        Ok(params)
+    }
+
+    /// Formats all parameters in the format accepted by `parse`.
+    pub fn to_text(&self) -> String {
+        Self::SPECS
+            .iter()
+            .map(|spec| format!("{} {}\n", spec.name, self.get(spec.name).unwrap()))
+            .collect()
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read `{}`: {e}", path.display()))?;
+        Self::parse(&text).map_err(|e| format!("`{}`: {e}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_text())
+            .map_err(|e| format!("Failed to write `{}`: {e}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_params() {
+        let mut params = Params::DEFAULT;
+        params.rfp_margin += 1;
+        assert_eq!(Params::parse(&params.to_text()), Ok(params));
+
+        let spsa = "# iteration 10\nrfp_margin 70.4\n\nfp_base 100 # comment\n";
+        let parsed = Params::parse(spsa).unwrap();
+        assert_eq!(parsed.rfp_margin, 70);
+        assert_eq!(parsed.fp_base, 100);
+        assert_eq!(parsed.fp_scale, Params::DEFAULT.fp_scale);
+
+        assert!(Params::parse("rfp_margin 100000").is_err());
+        assert!(Params::parse("no_such_param 1").is_err());
+        assert!(Params::parse("rfp_margin").is_err());
+    }
 }
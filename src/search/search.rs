@@ -9,11 +9,16 @@ use crate::{
         move_picker::{MovePicker, Stage},
         params::*,
         searcher::ThreadCtx,
+        stats::Stat,
+        trace::{Decision, Skip},
         transposition_table::TTFlag,
     },
     util::MAX_PLY,
 };
 
+#[cfg(feature = "trace")]
+use crate::search::trace::NodeKind;
+
 pub const DEPTH_SCALE: i32 = 1024;
 
 pub trait NodeType {
@@ -56,6 +61,34 @@ fn update_pv(thread: &mut ThreadCtx, ply: u16, mv: Move) {
 }
 
 pub fn search<Node: NodeType>(
+    pos: &mut Position,
+    depth: i32,
+    ply: u16,
+    alpha: Score,
+    beta: Score,
+    cutnode: bool,
+    thread: &mut ThreadCtx,
+) -> Score {
+    #[cfg(feature = "trace")]
+    {
+        let kind = if Node::ROOT {
+            NodeKind::Root
+        } else if Node::PV {
+            NodeKind::PV
+        } else {
+            NodeKind::NonPV
+        };
+        let mv = pos.prev_move(1).map(|(_, mv)| mv);
+        thread.tracer.enter(kind, ply, mv, depth, alpha, beta);
+    }
+    let score = search_node::<Node>(pos, depth, ply, alpha, beta, cutnode, thread);
+    thread.tracer.exit(ply, score);
+
+    score
+}
+
+#[inline(always)]
+fn search_node<Node: NodeType>(
     pos: &mut Position,
     mut depth: i32,
     ply: u16,
@@ -69,6 +102,7 @@ pub fn search<Node: NodeType>(
             thread.global.time_manager.set_stop_flag(true);
         }
         thread.abort_now = true;
+        thread.tracer.decide(ply, Decision::Abort);
         return Score::ZERO;
     }
 
@@ -84,25 +118,46 @@ pub fn search<Node: NodeType>(
 
         if alpha >= beta {
             thread.nodes.inc();
+            thread.stats.inc(Stat::Nodes);
+            thread.tracer.decide(ply, Decision::MateDistance);
             return alpha;
         }
     }
 
     if let Some(terminal) = pos.board().terminal_state() {
         thread.nodes.inc();
+        thread.stats.inc(Stat::Nodes);
+        thread.tracer.decide(ply, Decision::Terminal);
         return match terminal {
             TerminalState::Checkmate(_) => Score::new_mated(ply),
-            TerminalState::Draw => Score::ZERO,
+            TerminalState::Draw => thread.draw_scores[pos.board().stm()],
         };
     }
 
     if !Node::ROOT && pos.repetition() {
         thread.nodes.inc();
-        return Score::ZERO;
+        thread.stats.inc(Stat::Nodes);
+        thread.tracer.decide(ply, Decision::Repetition);
+        return thread.draw_scores[pos.board().stm()];
+    }
+
+    // If we can move into a repetition, we're guaranteed at least a draw.
+    let draw_score = thread.draw_scores[pos.board().stm()];
+    if !Node::ROOT && alpha < draw_score && pos.upcoming_repetition() {
+        alpha = draw_score;
+        thread.tracer.upcoming_repetition(ply);
+        if alpha >= beta {
+            thread.nodes.inc();
+            thread.stats.inc(Stat::Nodes);
+            thread.stats.inc(Stat::UpcomingRep);
+            thread.tracer.decide(ply, Decision::Repetition);
+            return alpha;
+        }
     }
 
     if ply >= MAX_PLY {
-        return pos.eval(&mut thread.nnue, thread.mat_scaling);
+        thread.tracer.decide(ply, Decision::MaxPly);
+        return pos.eval(&mut thread.nnue, thread.mat_scaling, &thread.params);
     }
 
     if depth <= 0 {
@@ -111,13 +166,22 @@ pub fn search<Node: NodeType>(
 
     if !Node::ROOT {
         thread.nodes.inc();
+        thread.stats.inc(Stat::Nodes);
     }
 
     let tt_entry = thread.global.ttable.fetch(pos.board().hash(), ply);
+    thread.tracer.tt_probe(ply, tt_entry);
+    thread.stats.inc(Stat::TtProbes);
+    if tt_entry.is_some() {
+        thread.stats.inc(Stat::TtHits);
+    }
     let tt_move = tt_entry.and_then(|e| e.mv);
     let tt_pv = Node::PV || tt_entry.is_some_and(|e| e.flags.pv());
     let singular = thread.search_stack[ply as usize].singular;
     let singular_search = singular.is_some();
+    // Searches that skip some moves mustn't leave their result behind for later searches.
+    let excluding_root = Node::ROOT && thread.excluding_root_moves;
+    let partial_search = singular_search || excluding_root;
 
     // TT cutoffs
     if !Node::PV
@@ -126,11 +190,16 @@ pub fn search<Node: NodeType>(
         && e.depth as i32 * DEPTH_SCALE >= depth
     {
         let score = e.score;
-        match e.flags.tt_flag() {
-            TTFlag::Exact => return score,
-            TTFlag::Lower if score >= beta => return score,
-            TTFlag::Upper if score <= alpha => return score,
-            _ => {}
+        let cutoff = match e.flags.tt_flag() {
+            TTFlag::Exact => true,
+            TTFlag::Lower => score >= beta,
+            TTFlag::Upper => score <= alpha,
+            TTFlag::None => false,
+        };
+        if cutoff {
+            thread.stats.inc(Stat::TtCutoffs);
+            thread.tracer.decide(ply, Decision::TTCutoff);
+            return score;
         }
     }
 
@@ -143,8 +212,12 @@ pub fn search<Node: NodeType>(
     } else {
         let raw_eval = tt_entry
             .map(|e| e.eval)
-            .unwrap_or_else(|| pos.eval(&mut thread.nnue, thread.mat_scaling));
-        let static_eval = Score::clamp_nomate(raw_eval.0.saturating_add(thread.history.corr(pos)));
+            .unwrap_or_else(|| pos.eval(&mut thread.nnue, thread.mat_scaling, &thread.params));
+        let static_eval = Score::clamp_nomate(
+            raw_eval
+                .0
+                .saturating_add(thread.history.corr(pos, &thread.params)),
+        );
         (raw_eval, static_eval)
     };
 
@@ -164,7 +237,7 @@ pub fn search<Node: NodeType>(
 
     thread.search_stack[ply as usize].static_eval = static_eval;
 
-    if !singular_search && !in_check && tt_entry.is_none() {
+    if !partial_search && !in_check && tt_entry.is_none() {
         thread.global.ttable.store(
             pos.board().hash(),
             0,
@@ -191,11 +264,12 @@ pub fn search<Node: NodeType>(
     if !Node::ROOT
         && !in_check
         && !singular_search
-        && thread.search_stack[ply as usize - 1].reduction >= hindsight_ext_min_red()
+        && thread.search_stack[ply as usize - 1].reduction >= thread.params.hindsight_ext_min_red
         && thread.search_stack[ply as usize - 1].static_eval != Score::NONE
         && static_eval < -thread.search_stack[ply as usize - 1].static_eval
     {
-        depth += hindsight_ext_ext();
+        depth += thread.params.hindsight_ext_ext;
+        thread.stats.inc(Stat::HindsightExt);
     }
 
     if !Node::PV && !in_check && !singular_search {
@@ -203,10 +277,12 @@ pub fn search<Node: NodeType>(
         let improving_depth = (depth / DEPTH_SCALE - improving as i32).max(0) as i16;
         if depth < rfp_depth()
             && score_estimate
-                - rfp_margin() * improving_depth
-                - rfp_quad_margin() * improving_depth.pow(2) / 128
+                - thread.params.rfp_margin * improving_depth
+                - thread.params.rfp_quad_margin * improving_depth.pow(2) / 128
                 >= beta
         {
+            thread.stats.inc(Stat::Rfp);
+            thread.tracer.decide(ply, Decision::Rfp);
             if !score_estimate.is_win() && !beta.is_win() {
                 return Score(score_estimate.0.midpoint(beta.0));
             } else {
@@ -224,6 +300,7 @@ pub fn search<Node: NodeType>(
         {
             pos.make_null_move();
             thread.global.ttable.prefetch(pos.board());
+            thread.stats.inc(Stat::NmpSearches);
 
             let nmp_reduction = nmp_red_base() + depth * 128 / nmp_red_scale_div();
             let score = -search::<NonPV>(
@@ -238,11 +315,15 @@ pub fn search<Node: NodeType>(
             pos.unmake_null_move();
 
             if thread.abort_now {
+                thread.tracer.decide(ply, Decision::Abort);
                 return Score::ZERO;
             }
 
             if score >= beta {
+                thread.stats.inc(Stat::NmpFailHighs);
                 if depth <= nmp_verif_min_depth() || thread.min_nmp_ply > 0 {
+                    thread.stats.inc(Stat::NmpCutoffs);
+                    thread.tracer.decide(ply, Decision::Nmp);
                     if score.is_win() {
                         return beta;
                     } else {
@@ -264,13 +345,15 @@ pub fn search<Node: NodeType>(
                 thread.min_nmp_ply = 0;
 
                 if verif_score >= beta {
+                    thread.stats.inc(Stat::NmpCutoffs);
+                    thread.tracer.decide(ply, Decision::Nmp);
                     return verif_score;
                 }
             }
         }
     }
 
-    let probcut_beta = beta.saturating_add(probcut_margin());
+    let probcut_beta = beta.saturating_add(thread.params.probcut_margin);
     if !Node::PV
         && !singular_search
         && !in_check
@@ -280,12 +363,14 @@ pub fn search<Node: NodeType>(
         && !beta.is_mate()
         && matches!(tte.flags.tt_flag(), TTFlag::Lower | TTFlag::Exact)
         && tte.score >= probcut_beta
-        && (tte.depth as i32) * DEPTH_SCALE >= depth - probcut_depth_offset()
+        && (tte.depth as i32) * DEPTH_SCALE >= depth - thread.params.probcut_depth_offset
     {
+        thread.stats.inc(Stat::Probcut);
+        thread.tracer.decide(ply, Decision::Probcut);
         return tte.score;
     }
 
-    let mut move_picker = MovePicker::new(tt_move, false, movepick_see_threshold());
+    let mut move_picker = MovePicker::new(tt_move, false, thread.params.movepick_see_threshold);
     let mut best_score = -Score::INFINITE;
     let mut moves_seen = 0;
     let mut best_move = None;
@@ -304,18 +389,23 @@ pub fn search<Node: NodeType>(
         }
 
         let is_tactic = pos.board().is_tactic(mv);
-        let mut lmr = get_lmr(is_tactic, (depth / DEPTH_SCALE) as u8, moves_seen);
+        let mut lmr = thread
+            .params
+            .get_lmr(is_tactic, (depth / DEPTH_SCALE) as u8, moves_seen);
         let mut extension = 0;
         let mut score;
 
         if !Node::ROOT && !best_score.is_loss() {
             if is_tactic {
                 // Tactic SEE Pruning
-                let see_margin = tactic_see_base() + (tactic_see_scale() * depth / DEPTH_SCALE);
+                let see_margin = thread.params.tactic_see_base
+                    + (thread.params.tactic_see_scale * depth / DEPTH_SCALE);
                 if depth <= see_max_depth()
                     && move_picker.stage() > Stage::YieldGoodNoisy
-                    && !pos.cmp_see(mv, see_margin)
+                    && !pos.cmp_see(mv, see_margin, &thread.params)
                 {
+                    thread.stats.inc(Stat::TacticSee);
+                    thread.tracer.skip(ply, mv, Skip::TacticSee);
                     continue;
                 }
             } else {
@@ -323,36 +413,46 @@ pub fn search<Node: NodeType>(
 
                 if !move_picker.no_more_quiets() {
                     // LMP
-                    let lmp_margin = (lmp_base()
-                        + lmp_scale() * ((lmr_depth / DEPTH_SCALE) as u32).pow(2))
+                    let lmp_margin = (thread.params.lmp_base
+                        + thread.params.lmp_scale * ((lmr_depth / DEPTH_SCALE) as u32).pow(2))
                         >> u32::from(!improving);
 
                     if moves_seen as u32 * 1024 >= lmp_margin {
+                        thread.stats.inc(Stat::Lmp);
+                        thread.tracer.skip(ply, mv, Skip::Lmp);
                         move_picker.skip_quiets();
                     }
 
                     // FP
-                    let fp_margin = fp_base() + (fp_scale() * lmr_depth / DEPTH_SCALE) as i16;
+                    let fp_margin = thread.params.fp_base
+                        + (thread.params.fp_scale * lmr_depth / DEPTH_SCALE) as i16;
                     if lmr_depth <= fp_depth()
                         && !in_check
                         && static_eval + fp_margin <= alpha
                         && !pos.board().gives_direct_check(mv)
                     {
+                        thread.stats.inc(Stat::Fp);
+                        thread.tracer.skip(ply, mv, Skip::Fp);
                         move_picker.skip_quiets();
                     }
 
                     // History pruning
                     let hist = thread.history.score_quiet(pos, mv);
-                    let hist_margin = -hist_prune_scale() * lmr_depth / DEPTH_SCALE;
+                    let hist_margin = -thread.params.hist_prune_scale * lmr_depth / DEPTH_SCALE;
                     if depth <= hist_prune_depth() && hist < hist_margin {
+                        thread.stats.inc(Stat::HistPrune);
+                        thread.tracer.skip(ply, mv, Skip::HistPrune);
                         move_picker.skip_quiets();
                         continue;
                     }
                 }
 
                 // Quiet SEE Pruning
-                let see_margin = quiet_see_base() + (quiet_see_scale() * lmr_depth / DEPTH_SCALE);
-                if lmr_depth <= see_max_depth() && !pos.cmp_see(mv, see_margin) {
+                let see_margin = thread.params.quiet_see_base
+                    + (thread.params.quiet_see_scale * lmr_depth / DEPTH_SCALE);
+                if lmr_depth <= see_max_depth() && !pos.cmp_see(mv, see_margin, &thread.params) {
+                    thread.stats.inc(Stat::QuietSee);
+                    thread.tracer.skip(ply, mv, Skip::QuietSee);
                     continue;
                 }
             }
@@ -363,34 +463,44 @@ pub fn search<Node: NodeType>(
             && depth >= se_min_depth()
             && let Some(tte) = tt_entry
             && tte.mv.is_some_and(|tt_mv| tt_mv == mv)
-            && tte.depth as i32 * DEPTH_SCALE >= (depth - se_tt_depth_offset())
+            && tte.depth as i32 * DEPTH_SCALE >= (depth - thread.params.se_tt_depth_offset)
             && tte.flags.tt_flag() != TTFlag::Upper
         {
             let s_beta = tte
                 .score
-                .saturating_add((-depth * se_beta_scale() / (DEPTH_SCALE * 128)) as i16)
+                .saturating_add((-depth * thread.params.se_beta_scale / (DEPTH_SCALE * 128)) as i16)
                 .max(-Score::MAX_MATE + 1);
             let s_depth = (depth - se_depth_offset()) * se_depth_scale() / 128;
 
             thread.search_stack[ply as usize].singular = Some(mv);
+            thread.stats.inc(Stat::SeSearches);
             let score = search::<NonPV>(pos, s_depth, ply, s_beta - 1, s_beta, cutnode, thread);
             thread.search_stack[ply as usize].singular = None;
 
             if score < s_beta {
-                extension = se_single_ext();
+                extension = thread.params.se_single_ext;
                 // double extension
-                extension +=
-                    se_double_ext() * i32::from(!Node::PV && score + se_dext_margin() < beta);
+                if !Node::PV && score + thread.params.se_dext_margin < beta {
+                    extension += thread.params.se_double_ext;
+                    thread.stats.inc(Stat::SeDoubleExt);
+                } else {
+                    thread.stats.inc(Stat::SeSingleExt);
+                }
             } else if s_beta >= beta {
+                thread.stats.inc(Stat::SeMulticut);
+                thread.tracer.decide(ply, Decision::Multicut);
                 return s_beta;
             } else if tte.score >= beta {
-                extension = se_triple_negext();
+                extension = thread.params.se_triple_negext;
+                thread.stats.inc(Stat::SeTripleNegext);
             } else if cutnode {
                 // double negext
-                extension = se_double_negext();
+                extension = thread.params.se_double_negext;
+                thread.stats.inc(Stat::SeDoubleNegext);
             } else if tte.score <= alpha {
                 // negext
-                extension = se_single_negext();
+                extension = thread.params.se_single_negext;
+                thread.stats.inc(Stat::SeSingleNegext);
             }
         }
 
@@ -398,7 +508,7 @@ pub fn search<Node: NodeType>(
         let new_depth = depth + extension - DEPTH_SCALE;
 
         let hist_lmr = if pos.board().is_quiet(mv) {
-            thread.history.score_quiet(pos, mv) / quiet_hist_lmr_div()
+            thread.history.score_quiet(pos, mv) / thread.params.quiet_hist_lmr_div
         } else {
             0
         };
@@ -421,11 +531,11 @@ pub fn search<Node: NodeType>(
             if depth < lmr_min_depth() {
                 lmr = 0;
             } else {
-                lmr += lmr_nonpv() * !Node::PV as i32;
-                lmr -= lmr_ttpv() * tt_pv as i32;
-                lmr -= lmr_check() * pos.board().checkers().is_non_empty() as i32;
-                lmr += lmr_cutnode() * cutnode as i32;
-                lmr -= DEPTH_SCALE * hist_lmr as i32;
+                lmr += thread.params.lmr_nonpv * !Node::PV as i32;
+                lmr -= thread.params.lmr_ttpv * tt_pv as i32;
+                lmr -= thread.params.lmr_check * pos.board().checkers().is_non_empty() as i32;
+                lmr += thread.params.lmr_cutnode * cutnode as i32;
+                lmr -= DEPTH_SCALE * hist_lmr;
             }
 
             let lmr_depth = (new_depth - lmr).max(DEPTH_SCALE).min(new_depth);
@@ -433,8 +543,12 @@ pub fn search<Node: NodeType>(
             thread.search_stack[ply as usize].reduction = lmr;
             score = -search::<NonPV>(pos, lmr_depth, ply + 1, -alpha - 1, -alpha, true, thread);
             thread.search_stack[ply as usize].reduction = 0;
+            if lmr > 0 {
+                thread.stats.inc(Stat::LmrSearches);
+            }
 
             if lmr > 0 && score > alpha {
+                thread.stats.inc(Stat::LmrResearches);
                 score = -search::<NonPV>(
                     pos,
                     new_depth,
@@ -446,6 +560,7 @@ pub fn search<Node: NodeType>(
                 )
             }
             if Node::PV && score > alpha {
+                thread.stats.inc(Stat::PvsResearches);
                 score = -search::<PV>(pos, new_depth, ply + 1, -beta, -alpha, false, thread);
             }
         }
@@ -462,6 +577,7 @@ pub fn search<Node: NodeType>(
         }
 
         if thread.abort_now {
+            thread.tracer.decide(ply, Decision::Abort);
             return Score::ZERO;
         }
 
@@ -481,9 +597,20 @@ pub fn search<Node: NodeType>(
 
         if score >= beta {
             flag = TTFlag::Lower;
-            thread
-                .history
-                .update(pos, mv, &quiets, &tactics, (depth / DEPTH_SCALE) as i16);
+            thread.stats.inc(Stat::BetaCutoffs);
+            if moves_seen == 1 {
+                thread.stats.inc(Stat::FirstMoveCutoffs);
+            }
+            if !excluding_root {
+                thread.history.update(
+                    pos,
+                    mv,
+                    &quiets,
+                    &tactics,
+                    (depth / DEPTH_SCALE) as i16,
+                    &thread.params,
+                );
+            }
             break;
         }
 
@@ -496,7 +623,7 @@ pub fn search<Node: NodeType>(
         }
     }
 
-    if !singular_search {
+    if !partial_search {
         thread.global.ttable.store(
             pos.board().hash(),
             (depth / DEPTH_SCALE) as u8,
@@ -510,7 +637,7 @@ pub fn search<Node: NodeType>(
     }
 
     if !in_check
-        && !singular_search
+        && !partial_search
         && best_move.is_none_or(|mv| pos.board().is_quiet(mv))
         && match flag {
             TTFlag::Lower => best_score > static_eval,
@@ -518,15 +645,40 @@ pub fn search<Node: NodeType>(
             _ => true,
         }
     {
-        thread
-            .history
-            .update_corr(pos, (depth / DEPTH_SCALE) as i16, best_score, static_eval);
+        thread.history.update_corr(
+            pos,
+            (depth / DEPTH_SCALE) as i16,
+            best_score,
+            static_eval,
+            &thread.params,
+        );
     }
 
     best_score
 }
 
 pub fn qsearch<Node: NodeType>(
+    pos: &mut Position,
+    ply: u16,
+    alpha: Score,
+    beta: Score,
+    thread: &mut ThreadCtx,
+) -> Score {
+    #[cfg(feature = "trace")]
+    {
+        let mv = pos.prev_move(1).map(|(_, mv)| mv);
+        thread
+            .tracer
+            .enter(NodeKind::QSearch, ply, mv, 0, alpha, beta);
+    }
+    let score = qsearch_node::<Node>(pos, ply, alpha, beta, thread);
+    thread.tracer.exit(ply, score);
+
+    score
+}
+
+#[inline(always)]
+fn qsearch_node<Node: NodeType>(
     pos: &mut Position,
     ply: u16,
     mut alpha: Score,
@@ -535,9 +687,12 @@ pub fn qsearch<Node: NodeType>(
 ) -> Score {
     if thread.abort_now || thread.global.time_manager.stop_search(thread) {
         thread.abort_now = true;
+        thread.tracer.decide(ply, Decision::Abort);
         return Score::ZERO;
     }
     thread.nodes.inc();
+    thread.stats.inc(Stat::Nodes);
+    thread.stats.inc(Stat::QsNodes);
     thread.sel_depth = thread.sel_depth.max(ply);
 
     if Node::PV {
@@ -545,22 +700,41 @@ pub fn qsearch<Node: NodeType>(
     }
 
     if let Some(terminal) = pos.board().terminal_state() {
+        thread.tracer.decide(ply, Decision::Terminal);
         return match terminal {
             TerminalState::Checkmate(_) => Score::new_mated(ply),
-            TerminalState::Draw => Score::ZERO,
+            TerminalState::Draw => thread.draw_scores[pos.board().stm()],
         };
     }
 
     if pos.repetition() {
-        return Score::ZERO;
+        thread.tracer.decide(ply, Decision::Repetition);
+        return thread.draw_scores[pos.board().stm()];
+    }
+
+    let draw_score = thread.draw_scores[pos.board().stm()];
+    if alpha < draw_score && pos.upcoming_repetition() {
+        alpha = draw_score;
+        thread.tracer.upcoming_repetition(ply);
+        if alpha >= beta {
+            thread.stats.inc(Stat::UpcomingRep);
+            thread.tracer.decide(ply, Decision::Repetition);
+            return alpha;
+        }
     }
 
     if ply >= MAX_PLY {
-        return pos.eval(&mut thread.nnue, thread.mat_scaling);
+        thread.tracer.decide(ply, Decision::MaxPly);
+        return pos.eval(&mut thread.nnue, thread.mat_scaling, &thread.params);
     }
 
     let in_check = pos.board().checkers().is_non_empty();
     let tt_entry = thread.global.ttable.fetch(pos.board().hash(), ply);
+    thread.tracer.tt_probe(ply, tt_entry);
+    thread.stats.inc(Stat::TtProbes);
+    if tt_entry.is_some() {
+        thread.stats.inc(Stat::TtHits);
+    }
     let tt_pv = Node::PV || tt_entry.is_some_and(|e| e.flags.pv());
 
     let mut raw_eval = Score::NONE;
@@ -569,10 +743,12 @@ pub fn qsearch<Node: NodeType>(
     if !in_check {
         raw_eval = tt_entry
             .map(|e| e.eval)
-            .unwrap_or_else(|| pos.eval(&mut thread.nnue, thread.mat_scaling));
-        static_eval = raw_eval + thread.history.corr(pos);
+            .unwrap_or_else(|| pos.eval(&mut thread.nnue, thread.mat_scaling, &thread.params));
+        static_eval = raw_eval + thread.history.corr(pos, &thread.params);
 
         if static_eval >= beta {
+            thread.stats.inc(Stat::QsStandPat);
+            thread.tracer.decide(ply, Decision::StandPat);
             if static_eval.max(beta).is_win() {
                 return static_eval;
             } else {
@@ -603,11 +779,16 @@ pub fn qsearch<Node: NodeType>(
         && let Some(e) = tt_entry
     {
         let score = e.score;
-        match e.flags.tt_flag() {
-            TTFlag::Exact => return score,
-            TTFlag::Lower if score >= beta => return score,
-            TTFlag::Upper if score <= alpha => return score,
-            _ => {}
+        let cutoff = match e.flags.tt_flag() {
+            TTFlag::Exact => true,
+            TTFlag::Lower => score >= beta,
+            TTFlag::Upper => score <= alpha,
+            TTFlag::None => false,
+        };
+        if cutoff {
+            thread.stats.inc(Stat::TtCutoffs);
+            thread.tracer.decide(ply, Decision::TTCutoff);
+            return score;
         }
     }
 
@@ -615,26 +796,31 @@ pub fn qsearch<Node: NodeType>(
     let mut best_move = None;
     let mut flag = TTFlag::Upper;
     let mut moves_seen = 0;
-    let mut move_picker = MovePicker::new(None, !in_check, qs_see_threshold());
-    let futility = static_eval.saturating_add(qsfp_margin());
+    let mut move_picker = MovePicker::new(None, !in_check, thread.params.qs_see_threshold);
+    let futility = static_eval.saturating_add(thread.params.qsfp_margin);
 
     while let Some(mv) = move_picker.next(pos, thread) {
         if !best_score.is_loss() {
             // LMP
             if !in_check && moves_seen > qs_lmp_limit() {
+                thread.tracer.skip(ply, mv, Skip::QsLmp);
                 break;
             }
             // SEE Pruning
             if move_picker.stage() >= Stage::YieldBadNoisy {
+                thread.tracer.skip(ply, mv, Skip::QsSee);
                 break;
             }
             // Skip quiets if non-mated evasion was found
             move_picker.skip_quiets();
             if pos.board().is_quiet(mv) {
+                thread.tracer.skip(ply, mv, Skip::QsQuiet);
                 continue;
             }
             // FP
-            if !in_check && futility <= alpha && !pos.cmp_see(mv, 1) {
+            if !in_check && futility <= alpha && !pos.cmp_see(mv, 1, &thread.params) {
+                thread.stats.inc(Stat::QsFp);
+                thread.tracer.skip(ply, mv, Skip::QsFp);
                 best_score = best_score.max(futility);
                 continue;
             }
@@ -651,6 +837,7 @@ pub fn qsearch<Node: NodeType>(
         }
 
         if thread.abort_now {
+            thread.tracer.decide(ply, Decision::Abort);
             return Score::ZERO;
         }
 
@@ -1,10 +1,11 @@
 use std::{
     sync::{
-        Arc,
+        Arc, Mutex,
         atomic::{
             AtomicU32, AtomicU64,
             Ordering::{Acquire, Relaxed, Release},
         },
+        mpsc,
     },
     thread::{self, JoinHandle},
     time::Duration,
@@ -12,6 +13,10 @@ use std::{
 
 use arrayvec::ArrayVec;
 use icarus_board::{board::Board, r#move::Move};
+use icarus_common::{
+    piece::Color,
+    util::enum_map::{EnumMap, enum_map},
+};
 
 use crate::{
     nnue::network::Nnue,
@@ -19,9 +24,12 @@ use crate::{
     score::Score,
     search::{
         history::History,
-        params::{asp_initial_window, asp_min_depth, asp_widen_factor},
+        params::{Params, asp_min_depth},
         search::{DEPTH_SCALE, Root, search},
+        skill::{SKILL_CANDIDATES, Skill},
+        stats::SearchStats,
         time_manager::TimeManager,
+        trace::{TraceConfig, Tracer},
         transposition_table::{DEFAULT_TT_SIZE, TTFlag, TTable},
     },
     uci::SearchLimit,
@@ -29,6 +37,7 @@ use crate::{
         MAX_PLY,
         buffered_counter::BufferedCounter,
         command_channel::{Receiver, Sender, channel},
+        turnstile::Turnstile,
     },
     wdl,
 };
@@ -43,10 +52,29 @@ pub struct GlobalCtx {
     /// If not in search, 0.
     pub num_searching: AtomicU32,
     pub ttable: TTable,
+    /// Statistics of the last search, merged from all threads at the end of `id_loop`.
+    pub stats: Mutex<SearchStats>,
+    /// Used to let the threads take turns in deterministic searches.
+    pub turnstile: Arc<Turnstile>,
+    /// Results of the current search, pushed by each thread at the end of `id_loop`, and
+    /// taken by the main thread once all threads are done.
+    pub results: Mutex<Vec<ThreadResult>>,
+    /// The result the main thread picked in the last search.
+    pub last_result: Mutex<Option<ThreadResult>>,
 }
 
 pub type PrincipalVariation = ArrayVec<Move, { MAX_PLY as usize }>;
 
+/// The last completed iteration of a single search thread.
+#[derive(Clone)]
+pub struct ThreadResult {
+    pub id: usize,
+    pub depth: u16,
+    pub sel_depth: u16,
+    pub score: Score,
+    pub pv: PrincipalVariation,
+}
+
 pub struct ThreadCtx {
     pub id: usize,
     pub global: Arc<GlobalCtx>,
@@ -58,9 +86,22 @@ pub struct ThreadCtx {
     pub root_moves: Vec<Move>,
     /// Count the number of nodes spent searching each root move.
     pub root_move_nodes: [[u64; 64]; 64],
+    /// Set while searching skill candidates with the earlier candidates removed from
+    /// `root_moves`. The root result isn't the best move of the position then, so it must not be
+    /// stored in the TT or used to update correction history.
+    pub excluding_root_moves: bool,
     pub sel_depth: u16,
+    /// The search parameters of the current search.
+    pub params: Params,
+    /// Score of a drawn position for each side to move. These only depend on the root color,
+    /// so that they are consistent across all nodes of a search.
+    pub draw_scores: EnumMap<Color, Score>,
     pub search_stack: Box<[SearchStackEntry; MAX_PLY as usize + 1]>,
+    /// PV of the last completed iteration. For the main thread, this is replaced by the PV
+    /// of the chosen bestmove at the end of the search.
     pub root_pv: PrincipalVariation,
+    pub stats: SearchStats,
+    pub tracer: Tracer,
 
     // Used for NMP verification search
     pub min_nmp_ply: u16,
@@ -83,11 +124,16 @@ impl ThreadCtx {
             nodes,
             root_moves: vec![],
             root_move_nodes: [[0; 64]; 64],
+            excluding_root_moves: false,
             sel_depth: 0,
+            params: Params::DEFAULT,
+            draw_scores: enum_map! { _ => Score::ZERO },
             search_stack: vec![Default::default(); MAX_PLY as usize + 1]
                 .try_into()
                 .unwrap(),
             root_pv: Default::default(),
+            stats: Default::default(),
+            tracer: Default::default(),
             history: History::new(),
             nnue: Nnue::new(&Board::start_pos()),
             min_nmp_ply: 0,
@@ -104,9 +150,47 @@ impl ThreadCtx {
         self.search_stack.fill(Default::default());
         self.root_move_nodes = [[0; 64]; 64];
         self.abort_now = false;
-        self.nnue.full_reset(search_params.pos.board());
+        self.stats.clear();
+        let root = search_params.pos.board();
+        self.nnue.full_reset(root);
+        // Contempt is the amount by which the root side considers a draw worse than equality. With
+        // a WDL head, it grows with how much more likely the net thinks a win is than a loss.
+        let mut contempt = search_params.contempt;
+        if let Some([w, _, l]) = self.nnue.wdl(root.stm()) {
+            contempt =
+                contempt + Score((search_params.wdl_contempt.0 as f64 * (w - l)).round() as i16);
+        }
+        let contempt = wdl::denormalize(contempt, root.classical_material());
+        self.draw_scores = enum_map! {
+            color => if color == root.stm() { -contempt } else { contempt },
+        };
+        self.params = search_params.params;
+        self.nodes.set_turnstile(
+            search_params
+                .deterministic
+                .then(|| (self.global.turnstile.clone(), self.id)),
+        );
+        #[cfg(feature = "trace")]
+        if self.id == 0 {
+            self.tracer.start(
+                &search_params.trace,
+                &search_params.pos.board().fen(self.chess960),
+                self.chess960,
+            );
+        }
 
-        id_loop(search_params.pos, self, search_params.print_info)
+        if search_params.deterministic {
+            self.global.turnstile.wait(self.id);
+        }
+
+        id_loop(
+            search_params.pos,
+            self,
+            search_params.print_info,
+            search_params.debug,
+            search_params.deterministic,
+            search_params.skill,
+        )
     }
 }
 
@@ -135,6 +219,22 @@ pub struct SearchParams {
     pub root_moves: Option<Vec<Move>>,
     pub chess960: bool,
     pub print_info: Print,
+    /// Print the search statistics after the search. Only has an effect with the `stats` feature.
+    pub debug: bool,
+    /// Only has an effect with the `trace` feature.
+    pub trace: TraceConfig,
+    /// Serialize the threads: only one thread searches at a time, and hands over to the next one
+    /// after every `FLUSH_THRESHOLD` nodes and every iteration. Which thread sees which TT and
+    /// node count state then only depends on the node/depth limits and the number of threads, so
+    /// the search result is reproducible, at the cost of not searching in parallel.
+    pub deterministic: bool,
+    pub skill: Skill,
+    /// Draw score in centipawns, from the perspective of the root side to move.
+    pub contempt: Score,
+    /// Extra contempt in centipawns at a predicted win-loss difference of 100%. Only has an effect
+    /// with nets that have a WDL head.
+    pub wdl_contempt: Score,
+    pub params: Params,
 }
 
 #[derive(Clone)]
@@ -144,6 +244,8 @@ enum ThreadCmd {
     Search(Box<SearchParams>),
     SetGlobal(Arc<GlobalCtx>),
     NewGame,
+    /// Asks the main thread for its correction history value of a position.
+    Corr(Box<Position>, mpsc::Sender<i16>),
     Quit,
 }
 
@@ -158,6 +260,14 @@ pub struct Searcher {
     pub global_ctx: Arc<GlobalCtx>,
     search_threads: Vec<JoinHandle<()>>,
     command_sender: Sender<ThreadCmd>,
+    debug: bool,
+    trace: TraceConfig,
+    deterministic: bool,
+    nodes_time: u64,
+    skill: Skill,
+    contempt: Score,
+    wdl_contempt: Score,
+    params: Params,
 }
 
 impl Default for Searcher {
@@ -167,6 +277,10 @@ impl Default for Searcher {
             nodes: Arc::new(AtomicU64::new(0)),
             num_searching: AtomicU32::new(0),
             ttable: TTable::new(DEFAULT_TT_SIZE),
+            stats: Default::default(),
+            turnstile: Default::default(),
+            results: Default::default(),
+            last_result: Default::default(),
         });
         let (mut tx, mut rx) = channel(1);
         let search_thread = thread::spawn({
@@ -187,6 +301,14 @@ impl Default for Searcher {
             global_ctx,
             search_threads: vec![search_thread],
             command_sender: tx,
+            debug: false,
+            trace: Default::default(),
+            deterministic: false,
+            nodes_time: 0,
+            skill: Default::default(),
+            contempt: Score::ZERO,
+            wdl_contempt: Score::ZERO,
+            params: Params::DEFAULT,
         }
     }
 }
@@ -211,6 +333,8 @@ impl Searcher {
         );
 
         self.global_ctx.nodes.store(0, Relaxed);
+        self.global_ctx.stats.lock().unwrap().clear();
+        self.global_ctx.turnstile.reset(self.search_threads.len());
         // We store one "pseudo"-searcher, to make sure that `is_running` never falsely
         // returns false
         self.global_ctx.num_searching.store(1, Relaxed);
@@ -220,7 +344,14 @@ impl Searcher {
             use_soft_nodes,
             true,
             move_overhead,
+            self.nodes_time,
+            &self.params,
         );
+        if self.skill.enabled() {
+            self.global_ctx
+                .time_manager
+                .restrict(self.skill.max_depth(), self.skill.max_nodes());
+        }
 
         let root_moves = limits.into_iter().find_map(|limit| match limit {
             SearchLimit::SearchMoves(moves) => Some(moves),
@@ -232,17 +363,82 @@ impl Searcher {
             root_moves,
             chess960,
             print_info,
+            debug: self.debug,
+            trace: self.trace.clone(),
+            deterministic: self.deterministic,
+            skill: self.skill,
+            contempt: self.contempt,
+            wdl_contempt: self.wdl_contempt,
+            params: self.params,
         });
 
         self.command_sender.send(ThreadCmd::Search(params));
     }
 
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+    }
+
+    pub fn set_trace(&mut self, trace: TraceConfig) {
+        self.trace = trace;
+    }
+
+    pub fn trace(&self) -> &TraceConfig {
+        &self.trace
+    }
+
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    /// Sets the number of nodes per millisecond used to convert time limits into node limits.
+    /// `0` disables the conversion.
+    pub fn set_nodes_time(&mut self, nodes_time: u64) {
+        self.nodes_time = nodes_time;
+    }
+
+    pub fn set_skill(&mut self, skill: Skill) {
+        self.skill = skill;
+    }
+
+    pub fn skill(&self) -> &Skill {
+        &self.skill
+    }
+
+    /// Sets the contempt in centipawns, from the perspective of the root side to move.
+    pub fn set_contempt(&mut self, contempt: Score) {
+        self.contempt = contempt;
+    }
+
+    /// Sets the contempt in centipawns that is added at a predicted win-loss difference of 100%,
+    /// scaled by the actual difference. Needs a net with a WDL head.
+    pub fn set_wdl_contempt(&mut self, wdl_contempt: Score) {
+        self.wdl_contempt = wdl_contempt;
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    pub fn set_params(&mut self, params: Params) {
+        self.params = params;
+    }
+
     pub fn newgame(&mut self) {
         assert!(!self.is_running(), "Called `newgame()` while searching");
         self.global_ctx.ttable.clear();
         self.command_sender.send(ThreadCmd::NewGame);
     }
 
+    /// Returns the correction the main thread's history applies to the static eval of `pos`.
+    pub fn corr(&mut self, pos: &Position) -> i16 {
+        assert!(!self.is_running(), "Called `corr()` while searching");
+        let (tx, rx) = mpsc::channel();
+        self.command_sender
+            .send(ThreadCmd::Corr(Box::new(pos.clone()), tx));
+        rx.recv().unwrap()
+    }
+
     pub fn quit(&mut self) {
         self.global_ctx.time_manager.set_stop_flag(true);
         self.command_sender.send(ThreadCmd::Quit);
@@ -275,6 +471,10 @@ impl Searcher {
             nodes: Default::default(),
             num_searching: Default::default(),
             ttable: TTable::new(mb),
+            stats: Default::default(),
+            turnstile: Default::default(),
+            results: Default::default(),
+            last_result: Default::default(),
         });
         self.command_sender
             .send(ThreadCmd::SetGlobal(self.global_ctx.clone()));
@@ -329,21 +529,36 @@ fn worker_thread_loop(mut rx: Receiver<ThreadCmd>, global: Arc<GlobalCtx>, id: u
             ThreadCmd::NewGame => {
                 thread_ctx.history.clear();
             }
+            ThreadCmd::Corr(pos, tx) => {
+                if id == 0 {
+                    let _ = tx.send(thread_ctx.history.corr(&pos, &thread_ctx.params));
+                }
+            }
             ThreadCmd::Quit => return,
         }
     }
 }
 
-pub fn id_loop(mut pos: Position, thread: &mut ThreadCtx, print: Print) -> Score {
+pub fn id_loop(
+    mut pos: Position,
+    thread: &mut ThreadCtx,
+    print: Print,
+    debug: bool,
+    deterministic: bool,
+    skill: Skill,
+) -> Score {
     let mut depth = 1;
     let mut best_score = -Score::INFINITE;
     let mut prev_move = None;
     let mut move_stability = 0;
+    let mut completed_depth = 0;
+    let mut completed_sel_depth = 0;
+    let mut candidates = vec![];
 
     'id: loop {
         thread.sel_depth = 0;
 
-        let mut delta = asp_initial_window();
+        let mut delta = thread.params.asp_initial_window;
         let mut alpha = best_score.saturating_add(-delta);
         let mut beta = best_score.saturating_add(delta);
 
@@ -395,13 +610,15 @@ pub fn id_loop(mut pos: Position, thread: &mut ThreadCtx, print: Print) -> Score
                     info_score,
                     bound,
                     depth,
+                    thread.sel_depth,
                     thread,
                     &pos,
                     &thread.search_stack[0].pv,
                 );
             }
 
-            delta = delta.saturating_add(((delta as i32) * asp_widen_factor() / 128) as i16);
+            delta = delta
+                .saturating_add(((delta as i32) * thread.params.asp_widen_factor / 128) as i16);
         }
 
         if thread.id == 0 {
@@ -422,6 +639,15 @@ pub fn id_loop(mut pos: Position, thread: &mut ThreadCtx, print: Print) -> Score
         }
 
         thread.root_pv = thread.search_stack[0].pv.clone();
+        completed_depth = depth;
+        completed_sel_depth = thread.sel_depth;
+        if skill.enabled() && thread.id == 0 {
+            match root_candidates(&mut pos, thread, depth, best_score) {
+                Some(c) => candidates = c,
+                None => break 'id,
+            }
+        }
+
         if depth >= MAX_PLY
             || thread
                 .global
@@ -438,6 +664,7 @@ pub fn id_loop(mut pos: Position, thread: &mut ThreadCtx, print: Print) -> Score
                 best_score,
                 TTFlag::Exact,
                 depth,
+                thread.sel_depth,
                 thread,
                 &pos,
                 &thread.root_pv,
@@ -445,12 +672,31 @@ pub fn id_loop(mut pos: Position, thread: &mut ThreadCtx, print: Print) -> Score
         }
 
         depth += 1;
+        // In deterministic searches, the turn is also handed over after every iteration, so that
+        // the point at which it's handed over doesn't depend on where the iteration ended.
+        thread.nodes.sync();
+    }
+
+    if deterministic {
+        thread.global.turnstile.finish(thread.id);
     }
 
     if thread.global.time_manager.infinite() {
         thread.global.time_manager.wait_for_stop();
     }
 
+    thread.global.stats.lock().unwrap().merge(&thread.stats);
+    thread.tracer.finish();
+    if !thread.root_pv.is_empty() {
+        thread.global.results.lock().unwrap().push(ThreadResult {
+            id: thread.id,
+            depth: completed_depth,
+            sel_depth: completed_sel_depth,
+            score: best_score,
+            pv: thread.root_pv.clone(),
+        });
+    }
+
     let last = thread.global.num_searching.fetch_sub(1, Release) == 2;
     // If we are the last thread to decrement, we want to wake the main thread,
     // unless we ourselves are the main thread.
@@ -460,6 +706,7 @@ pub fn id_loop(mut pos: Position, thread: &mut ThreadCtx, print: Print) -> Score
         atomic_wait::wake_all(&thread.global.num_searching);
     }
 
+    let mut result = None;
     if thread.id == 0 {
         // The main thread must ensure that all other searchers are done before it prints
         // the bestmove, so we wait here unless we were the last thread already.
@@ -471,27 +718,60 @@ pub fn id_loop(mut pos: Position, thread: &mut ThreadCtx, print: Print) -> Score
             }
         }
 
+        // All results are in, so we can pick the best move. This has to happen before
+        // resetting `num_searching`, as a new search would clear the results.
+        let mut results = std::mem::take(&mut *thread.global.results.lock().unwrap());
+        result = if candidates.is_empty() {
+            vote(&mut results).cloned()
+        } else {
+            Some(skill.pick(&mut candidates, &mut rand::rng()).clone())
+        };
+        if let Some(result) = &result {
+            thread.root_pv = result.pv.clone();
+        }
+        thread
+            .global
+            .last_result
+            .lock()
+            .unwrap()
+            .clone_from(&result);
+
         // Because of the Release-Acquire pairs, we know that all other threads are now
         // done searching, so we can set `num_searching` to 0, signaling to the searcher
         // that we can accept commands again.
         thread.global.num_searching.store(0, Release);
     }
 
-    let best_move = *thread
-        .root_pv
-        .first()
-        .or(thread.root_moves.first())
-        .unwrap();
-
     if print != Print::None && thread.id == 0 {
-        print_info(
-            best_score,
-            TTFlag::Exact,
-            depth,
-            thread,
-            &pos,
-            &thread.root_pv,
-        );
+        let best_move = match &result {
+            Some(result) => {
+                print_info(
+                    result.score,
+                    TTFlag::Exact,
+                    result.depth,
+                    result.sel_depth,
+                    thread,
+                    &pos,
+                    &result.pv,
+                );
+                result.pv[0]
+            }
+            None => {
+                print_info(
+                    best_score,
+                    TTFlag::Exact,
+                    depth,
+                    thread.sel_depth,
+                    thread,
+                    &pos,
+                    &thread.root_pv,
+                );
+                *thread.root_moves.first().unwrap()
+            }
+        };
+        if debug {
+            thread.global.stats.lock().unwrap().print();
+        }
         println!("bestmove {}", best_move.display(thread.chess960));
     }
 
@@ -504,10 +784,95 @@ pub fn id_loop(mut pos: Position, thread: &mut ThreadCtx, print: Print) -> Score
     best_score
 }
 
+/// Searches the best `SKILL_CANDIDATES` root moves at the given depth, MultiPV-style, by
+/// repeatedly excluding the moves found so far from the root moves. The first candidate is the
+/// result of the regular iteration. Returns `None` if the search was aborted.
+fn root_candidates(
+    pos: &mut Position,
+    thread: &mut ThreadCtx,
+    depth: u16,
+    best_score: Score,
+) -> Option<Vec<ThreadResult>> {
+    let root_moves = thread.root_moves.clone();
+    thread.excluding_root_moves = true;
+    let mut candidates = vec![ThreadResult {
+        id: thread.id,
+        depth,
+        sel_depth: thread.sel_depth,
+        score: best_score,
+        pv: thread.root_pv.clone(),
+    }];
+
+    while candidates.len() < SKILL_CANDIDATES.min(root_moves.len()) {
+        thread
+            .root_moves
+            .retain(|mv| candidates.iter().all(|c| c.pv[0] != *mv));
+        thread.sel_depth = 0;
+
+        let score = search::<Root>(
+            pos,
+            (depth as i32) * DEPTH_SCALE,
+            0,
+            -Score::INFINITE,
+            Score::INFINITE,
+            false,
+            thread,
+        );
+        thread.nodes.flush();
+        if thread.abort_now {
+            break;
+        }
+
+        candidates.push(ThreadResult {
+            id: thread.id,
+            depth,
+            sel_depth: thread.sel_depth,
+            score,
+            pv: thread.search_stack[0].pv.clone(),
+        });
+    }
+
+    thread.root_moves = root_moves;
+    thread.excluding_root_moves = false;
+    (!thread.abort_now).then_some(candidates)
+}
+
+/// Picks the final result out of all threads' results. Each thread votes for its best move,
+/// weighted by its completed depth and by how much its score exceeds the lowest score of all
+/// threads. Proven wins take precedence over the vote, and the shortest one is preferred.
+fn vote(results: &mut [ThreadResult]) -> Option<&ThreadResult> {
+    // Sort by thread id so that the choice doesn't depend on which thread finished first.
+    results.sort_unstable_by_key(|r| r.id);
+
+    let min_score = results.iter().map(|r| r.score.0 as i64).min()?;
+    let weight = |r: &ThreadResult| (r.score.0 as i64 - min_score + 14) * r.depth as i64;
+    let votes = |mv: Move| -> i64 { results.iter().filter(|r| r.pv[0] == mv).map(weight).sum() };
+
+    let mut best = &results[0];
+    for r in &results[1..] {
+        let (r_votes, best_votes) = (votes(r.pv[0]), votes(best.pv[0]));
+        let better = if best.score.is_win() {
+            r.score > best.score
+        } else {
+            r.score.is_win()
+                || (!r.score.is_loss()
+                    && (r_votes > best_votes
+                        || (r_votes == best_votes && weight(r) > weight(best))))
+        };
+
+        if better {
+            best = r;
+        }
+    }
+
+    Some(best)
+}
+
 fn print_info(
     score: Score,
     bound: TTFlag,
     depth: u16,
+    sel_depth: u16,
     thread: &ThreadCtx,
     pos: &Position,
     pv: &[Move],
@@ -517,6 +882,9 @@ fn print_info(
     let nps = ((nodes as f64) / (time_us.max(1) as f64) * 1e6) as u64;
     let time_ms = time_us / 1000;
     let hashfull = thread.global.ttable.hashfull();
+    let material = pos.board().classical_material();
+    let (w, l) = net_wdl(score, thread, pos, pv).unwrap_or_else(|| wdl::wdl_model(score, material));
+    let d = 1000 - w - l;
     let pv = {
         use std::fmt::Write;
         let mut s = String::new();
@@ -526,9 +894,6 @@ fn print_info(
         s.pop();
         s
     };
-    let material = pos.board().classical_material();
-    let (w, l) = wdl::wdl_model(score, material);
-    let d = 1000 - w - l;
     let score = wdl::normalize(score, material);
     let bound = match bound {
         TTFlag::Lower => " lowerbound",
@@ -538,6 +903,74 @@ fn print_info(
 
     println!(
         "info depth {} seldepth {} score {}{} wdl {} {} {} time {} nodes {} nps {} hashfull {} pv {}",
-        depth, thread.sel_depth, score, bound, w, d, l, time_ms, nodes, nps, hashfull, pv
+        depth, sel_depth, score, bound, w, d, l, time_ms, nodes, nps, hashfull, pv
     )
 }
+
+/// The WDL predicted by the net's WDL head at the end of `pv`, from the perspective of the root side
+/// to move, in permille. `None` if the net has no WDL head or the score is a mate score.
+fn net_wdl(score: Score, thread: &ThreadCtx, pos: &Position, pv: &[Move]) -> Option<(i16, i16)> {
+    if score.is_mate() || !thread.nnue.has_wdl() {
+        return None;
+    }
+
+    let mut board = *pos.board();
+    for &mv in pv {
+        board.make_move(mv);
+    }
+    let [mut w, _, mut l] = thread.nnue.wdl_of(&board)?;
+    if board.stm() != pos.board().stm() {
+        std::mem::swap(&mut w, &mut l);
+    }
+
+    Some(((w * 1000.0).round() as i16, (l * 1000.0).round() as i16))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering::Relaxed;
+
+    use icarus_board::board::Board;
+
+    use crate::{
+        bench::FENS,
+        position::Position,
+        search::{
+            searcher::{Print, Searcher},
+            time_manager::DEFAULT_MOVE_OVERHEAD,
+        },
+        uci::SearchLimit,
+    };
+
+    #[test]
+    fn deterministic_threads() {
+        let search = |fen: &str| {
+            let mut searcher = Searcher::default();
+            searcher.change_threads(4);
+            searcher.set_deterministic(true);
+            searcher.search(
+                Position::new(Board::read_fen(fen).unwrap()),
+                vec![SearchLimit::Nodes(20000)],
+                false,
+                false,
+                DEFAULT_MOVE_OVERHEAD,
+                Print::None,
+            );
+            searcher.wait();
+
+            let result = searcher
+                .global_ctx
+                .last_result
+                .lock()
+                .unwrap()
+                .clone()
+                .unwrap();
+            let nodes = searcher.global_ctx.nodes.load(Relaxed);
+            (result.pv, result.score, result.depth, nodes)
+        };
+
+        for fen in &FENS[..4] {
+            assert_eq!(search(fen), search(fen), "{fen}");
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Weakened play through `Skill Level` and `UCI_LimitStrength`/`UCI_Elo`. Below the maximum
+//! level, the search is limited in depth and nodes, and the main thread searches a few root
+//! candidates MultiPV-style on every iteration. The bestmove is then picked randomly among them,
+//! where the randomness is larger for weaker levels and for candidates with close scores.
+
+use rand::Rng;
+
+use crate::search::searcher::ThreadResult;
+
+pub const MAX_SKILL_LEVEL: u8 = 20;
+
+/// The Elo every skill level plays at, in the format printed by the `calibrate` subcommand.
+/// `UCI_Elo` is interpolated between these, so they have to be strictly increasing. Until the
+/// shipped net has been calibrated, these are evenly spaced between 1320 and 3000.
+#[rustfmt::skip]
+pub const LEVEL_ELO: [u16; MAX_SKILL_LEVEL as usize + 1] = [
+    1320, // 0
+    1404, // 1
+    1488, // 2
+    1572, // 3
+    1656, // 4
+    1740, // 5
+    1824, // 6
+    1908, // 7
+    1992, // 8
+    2076, // 9
+    2160, // 10
+    2244, // 11
+    2328, // 12
+    2412, // 13
+    2496, // 14
+    2580, // 15
+    2664, // 16
+    2748, // 17
+    2832, // 18
+    2916, // 19
+    3000, // 20
+];
+pub const MIN_ELO: u16 = LEVEL_ELO[0];
+pub const MAX_ELO: u16 = LEVEL_ELO[MAX_SKILL_LEVEL as usize];
+
+/// Number of root candidates the bestmove is picked from.
+pub const SKILL_CANDIDATES: usize = 4;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Skill {
+    pub level: u8,
+    pub limit_strength: bool,
+    pub elo: u16,
+}
+
+impl Default for Skill {
+    fn default() -> Self {
+        Self {
+            level: MAX_SKILL_LEVEL,
+            limit_strength: false,
+            elo: MIN_ELO,
+        }
+    }
+}
+
+impl Skill {
+    /// The effective skill level. If `limit_strength` is set, it is interpolated from `elo`
+    /// between the two closest levels in `LEVEL_ELO`, and may be fractional.
+    pub fn level(&self) -> f64 {
+        if self.limit_strength {
+            let elo = self.elo.clamp(MIN_ELO, MAX_ELO);
+            // The highest level below `MAX_SKILL_LEVEL` that plays at `elo` or weaker.
+            let i = LEVEL_ELO[..MAX_SKILL_LEVEL as usize].partition_point(|&e| e <= elo) - 1;
+            let (lo, hi) = (LEVEL_ELO[i], LEVEL_ELO[i + 1]);
+            i as f64 + (elo - lo) as f64 / (hi - lo) as f64
+        } else {
+            self.level.min(MAX_SKILL_LEVEL) as f64
+        }
+    }
+
+    /// Returns the Elo that a (possibly fractional) skill level plays at, interpolated from
+    /// `LEVEL_ELO` like in `level`.
+    pub fn level_to_elo(level: f64) -> f64 {
+        let level = level.clamp(0.0, MAX_SKILL_LEVEL as f64);
+        let i = (level as usize).min(MAX_SKILL_LEVEL as usize - 1);
+        let (lo, hi) = (LEVEL_ELO[i] as f64, LEVEL_ELO[i + 1] as f64);
+        lo + (hi - lo) * (level - i as f64)
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.level() < MAX_SKILL_LEVEL as f64
+    }
+
+    pub fn max_depth(&self) -> u16 {
+        1 + self.level() as u16
+    }
+
+    pub fn max_nodes(&self) -> u64 {
+        2f64.powf(8.0 + 0.6 * self.level()) as u64
+    }
+
+    /// Picks the bestmove out of the root candidates. Every candidate gets a bonus consisting
+    /// of a deterministic part, which grows with its distance to the best candidate, and a
+    /// random part, which grows with the score spread of all candidates. Both parts are
+    /// scaled by the weakness of the level.
+    pub fn pick<'a>(
+        &self,
+        candidates: &'a mut [ThreadResult],
+        rng: &mut impl Rng,
+    ) -> &'a ThreadResult {
+        candidates.sort_by_key(|c| std::cmp::Reverse(c.score));
+
+        let top = candidates[0].score.0 as f64;
+        let delta = (top - candidates.last().unwrap().score.0 as f64).min(100.0);
+        let weakness = 120.0 - 2.0 * self.level();
+
+        let mut best = 0;
+        let mut max_score = f64::NEG_INFINITY;
+        for (i, c) in candidates.iter().enumerate() {
+            let score = c.score.0 as f64;
+            let push = (weakness * (top - score) + delta * rng.random_range(0.0..weakness)) / 128.0;
+            if score + push >= max_score {
+                max_score = score + push;
+                best = i;
+            }
+        }
+
+        &candidates[best]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::search::skill::{LEVEL_ELO, MAX_ELO, MAX_SKILL_LEVEL, MIN_ELO, Skill};
+
+    #[test]
+    fn elo_maps_to_level_and_back() {
+        assert!(LEVEL_ELO.is_sorted_by(|a, b| a < b));
+
+        for elo in MIN_ELO..=MAX_ELO {
+            let skill = Skill {
+                level: 0,
+                limit_strength: true,
+                elo,
+            };
+            let level = skill.level();
+            assert!(
+                (0.0..=MAX_SKILL_LEVEL as f64).contains(&level),
+                "{elo}: {level}"
+            );
+            assert!(
+                (Skill::level_to_elo(level) - elo as f64).abs() < 1e-6,
+                "{elo}: {level}"
+            );
+        }
+        for (level, &elo) in LEVEL_ELO.iter().enumerate() {
+            assert_eq!(Skill::level_to_elo(level as f64), elo as f64);
+        }
+    }
+}
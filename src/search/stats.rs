@@ -0,0 +1,130 @@
+//! Per-thread search statistics. The counters are only compiled in with the `stats` feature.
+//! Without it, `SearchStats` is zero-sized and recording a statistic is a no-op, so the
+//! instrumentation in `search` and `qsearch` costs nothing in regular builds.
+
+macro_rules! define_stats {
+    ($($name:ident => $desc:literal $(/ $parent:ident)?,)*) => {
+        #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+        pub enum Stat {
+            $($name,)*
+        }
+
+        impl Stat {
+            pub const COUNT: usize = [$(Stat::$name),*].len();
+            pub const ALL: [Stat; Self::COUNT] = [$(Stat::$name),*];
+
+            pub fn desc(self) -> &'static str {
+                match self {
+                    $(Stat::$name => $desc,)*
+                }
+            }
+
+            /// The statistic that this one is reported as a percentage of, if any.
+            pub fn parent(self) -> Option<Stat> {
+                match self {
+                    $(Stat::$name => None$(.or(Some(Stat::$parent)))?,)*
+                }
+            }
+        }
+    };
+}
+
+define_stats!(
+    Nodes               => "nodes",
+    QsNodes             => "qsearch nodes" / Nodes,
+
+    UpcomingRep         => "upcoming repetition cutoffs",
+
+    TtProbes            => "tt probes",
+    TtHits              => "tt hits" / TtProbes,
+    TtCutoffs           => "tt cutoffs" / TtProbes,
+
+    BetaCutoffs         => "beta cutoffs",
+    FirstMoveCutoffs    => "first move cutoffs" / BetaCutoffs,
+
+    Rfp                 => "rfp prunes",
+    NmpSearches         => "nmp searches",
+    NmpFailHighs        => "nmp fail highs" / NmpSearches,
+    NmpCutoffs          => "nmp cutoffs" / NmpSearches,
+    Probcut             => "probcut prunes",
+    Lmp                 => "lmp skips",
+    Fp                  => "fp skips",
+    HistPrune           => "history prunes",
+    TacticSee           => "tactic see prunes",
+    QuietSee            => "quiet see prunes",
+
+    SeSearches          => "singular searches",
+    SeSingleExt         => "single extensions" / SeSearches,
+    SeDoubleExt         => "double extensions" / SeSearches,
+    SeMulticut          => "multicuts" / SeSearches,
+    SeTripleNegext      => "triple negative extensions" / SeSearches,
+    SeDoubleNegext      => "double negative extensions" / SeSearches,
+    SeSingleNegext      => "single negative extensions" / SeSearches,
+    HindsightExt        => "hindsight extensions",
+
+    LmrSearches         => "reduced searches",
+    LmrResearches       => "lmr re-searches" / LmrSearches,
+    PvsResearches       => "pvs re-searches",
+
+    QsStandPat          => "qsearch stand pat cutoffs" / QsNodes,
+    QsFp                => "qsearch fp prunes",
+);
+
+#[derive(Clone, Default)]
+pub struct SearchStats {
+    #[cfg(feature = "stats")]
+    counts: [u64; Stat::COUNT],
+}
+
+impl SearchStats {
+    #[inline(always)]
+    pub fn inc(&mut self, _stat: Stat) {
+        #[cfg(feature = "stats")]
+        {
+            self.counts[_stat as usize] += 1;
+        }
+    }
+
+    pub fn get(&self, _stat: Stat) -> u64 {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "stats")] {
+                self.counts[_stat as usize]
+            } else {
+                0
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn merge(&mut self, _other: &Self) {
+        #[cfg(feature = "stats")]
+        for (count, other) in self.counts.iter_mut().zip(_other.counts) {
+            *count += other;
+        }
+    }
+
+    pub fn print(&self) {
+        if !cfg!(feature = "stats") {
+            println!("info string Search statistics require building with `--features stats`");
+            return;
+        }
+
+        for stat in Stat::ALL {
+            let count = self.get(stat);
+            match stat.parent() {
+                Some(parent) => {
+                    let pct = 100.0 * count as f64 / self.get(parent).max(1) as f64;
+                    println!(
+                        "info string {:<28} {count:>12} ({pct:>6.2}% of {})",
+                        stat.desc(),
+                        parent.desc()
+                    );
+                }
+                None => println!("info string {:<28} {count:>12}", stat.desc()),
+            }
+        }
+    }
+}
@@ -1,18 +1,15 @@
 use std::{
-    sync::atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicU64, Ordering::Relaxed},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicU64, Ordering::Relaxed},
+    },
     time::{Duration, Instant},
 };
 
 use icarus_common::{piece::Color, util::enum_map::enum_map};
 
 use crate::{
-    search::{
-        params::{
-            hard_time_factor, move_stability_base, move_stability_min, move_stability_scale,
-            node_tm_base, node_tm_scale, soft_time_factor,
-        },
-        searcher::ThreadCtx,
-    },
+    search::{params::Params, searcher::ThreadCtx},
     uci::SearchLimit,
     util::{MAX_PLY, atomic_instant::AtomicInstant},
 };
@@ -30,6 +27,10 @@ pub struct TimeManager {
     base_time: AtomicU64,
     soft_time: AtomicU64,
     hard_time: AtomicU64,
+    /// If nonzero, time limits are converted into node limits, with this many nodes per millisecond.
+    nodes_time: AtomicU64,
+    /// The search parameters of the current search, which the time limits depend on.
+    params: Mutex<Params>,
 }
 
 pub const DEFAULT_MOVE_OVERHEAD: u64 = 20;
@@ -47,11 +48,14 @@ impl Default for TimeManager {
             base_time: AtomicU64::new(0),
             soft_time: AtomicU64::new(0),
             hard_time: AtomicU64::new(0),
+            nodes_time: AtomicU64::new(0),
+            params: Mutex::new(Params::DEFAULT),
         }
     }
 }
 
 impl TimeManager {
+    #[allow(clippy::too_many_arguments)]
     pub fn init(
         &self,
         stm: Color,
@@ -59,8 +63,12 @@ impl TimeManager {
         use_soft_nodes: bool,
         set_start_time: bool,
         move_overhead: u64,
+        nodes_time: u64,
+        params: &Params,
     ) {
         self.set_stop_flag(false);
+        *self.params.lock().unwrap() = *params;
+        self.nodes_time.store(nodes_time, Relaxed);
 
         let mut time = enum_map! { _ => u64::MAX };
         let mut inc = enum_map! { _ => 0 };
@@ -107,9 +115,9 @@ impl TimeManager {
 
         let (time, inc) = (time[stm], inc[stm]);
 
-        let hard_time = (((time as u128) * hard_time_factor() / 4096) as u64)
+        let hard_time = (((time as u128) * params.hard_time_factor / 4096) as u64)
             .min(time.saturating_sub(move_overhead));
-        let soft_time = (((time as u128 * soft_time_factor() / 4096) as u64)
+        let soft_time = (((time as u128 * params.soft_time_factor / 4096) as u64)
             .saturating_sub(move_overhead)
             + inc)
             .min(hard_time);
@@ -126,6 +134,14 @@ impl TimeManager {
         }
     }
 
+    /// Further restricts the depth and node limits of the current search. Unlike passing
+    /// them to `init`, this doesn't turn an infinite search into a finite one.
+    pub fn restrict(&self, max_depth: u16, max_nodes: u64) {
+        self.max_depth.fetch_min(max_depth, Relaxed);
+        self.soft_nodes.fetch_min(max_nodes, Relaxed);
+        self.hard_nodes.fetch_min(max_nodes, Relaxed);
+    }
+
     pub fn set_stop_flag(&self, stop: bool) {
         self.stop.store(stop as u32, Relaxed);
         if self.infinite() {
@@ -147,7 +163,7 @@ impl TimeManager {
             || (thread.nodes.local().is_multiple_of(1024)
                 && thread.id == 0
                 && self.check_time.load(Relaxed)
-                && self.elapsed().as_millis() as u64 > self.hard_time.load(Relaxed))
+                && self.used_time(thread.nodes.global()) > self.hard_time.load(Relaxed))
     }
 
     pub fn stop_id(&self, depth: u16, nodes: u64) -> bool {
@@ -155,13 +171,21 @@ impl TimeManager {
             || depth >= self.max_depth.load(Relaxed)
             || nodes >= self.soft_nodes.load(Relaxed)
             || (self.check_time.load(Relaxed)
-                && self.elapsed().as_millis() as u64 > self.soft_time.load(Relaxed))
+                && self.used_time(nodes) > self.soft_time.load(Relaxed))
     }
 
     pub fn elapsed(&self) -> Duration {
         self.start.load(Relaxed).elapsed()
     }
 
+    /// Returns the time spent searching in milliseconds, which is measured in nodes if `nodes_time` is set.
+    fn used_time(&self, nodes: u64) -> u64 {
+        match self.nodes_time.load(Relaxed) {
+            0 => self.elapsed().as_millis() as u64,
+            nodes_per_ms => nodes / nodes_per_ms,
+        }
+    }
+
     pub fn wait_for_stop(&self) {
         while !self.stop_flag() {
             atomic_wait::wait(&self.stop, 0);
@@ -174,12 +198,13 @@ impl TimeManager {
         }
 
         let ratio = (best_move_nodes as f64) / (total_nodes.max(1) as f64);
+        let params = *self.params.lock().unwrap();
 
         let node_tm_factor =
-            (node_tm_base() as f64 / 1024.0) - (node_tm_scale() as f64 / 1024.0) * ratio;
-        let move_stability_factor = ((move_stability_base() as f64 / 1024.0)
-            - (move_stability_scale() as f64 / 1024.0) * (move_stability as f64))
-            .max(move_stability_min() as f64 / 1024.0);
+            (params.node_tm_base as f64 / 1024.0) - (params.node_tm_scale as f64 / 1024.0) * ratio;
+        let move_stability_factor = ((params.move_stability_base as f64 / 1024.0)
+            - (params.move_stability_scale as f64 / 1024.0) * (move_stability as f64))
+            .max(params.move_stability_min as f64 / 1024.0);
 
         let new_target =
             ((self.base_time.load(Relaxed) as f64 * node_tm_factor * move_stability_factor) as u64)
@@ -0,0 +1,325 @@
+//! Search tree tracing, compiled in only with the `trace` feature. When enabled, the main thread
+//! records every node visited by `search` and `qsearch` up to a configurable ply, and writes them
+//! to a file as JSON lines. Each line describes one node, and is written once the node returns, so
+//! children always precede their parent. The tree can be reconstructed from the `id` and `parent`
+//! fields. Besides the reason why it returned, every node lists the moves its move loop skipped
+//! without searching them, and whether alpha was raised to the draw score because of an upcoming
+//! repetition.
+//!
+//! Without the `trace` feature, `Tracer` is zero-sized and all of its methods are no-ops.
+
+use std::path::PathBuf;
+
+#[cfg(feature = "trace")]
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use icarus_board::r#move::Move;
+
+#[cfg(feature = "trace")]
+use crate::search::search::DEPTH_SCALE;
+use crate::{score::Score, search::transposition_table::TTEntry};
+
+#[derive(Clone, Debug)]
+pub struct TraceConfig {
+    /// File to write the trace of the next search to. Tracing is disabled if this is `None`.
+    pub path: Option<PathBuf>,
+    /// Nodes with a ply larger than this aren't recorded.
+    pub max_ply: u16,
+}
+
+pub const DEFAULT_TRACE_PLY: u16 = 4;
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_ply: DEFAULT_TRACE_PLY,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NodeKind {
+    Root,
+    PV,
+    NonPV,
+    QSearch,
+}
+
+/// The reason why a node returned.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Decision {
+    Searched,
+    Abort,
+    MateDistance,
+    Terminal,
+    Repetition,
+    MaxPly,
+    TTCutoff,
+    Rfp,
+    Nmp,
+    Probcut,
+    Multicut,
+    StandPat,
+}
+
+impl Decision {
+    pub fn name(self) -> &'static str {
+        match self {
+            Decision::Searched => "searched",
+            Decision::Abort => "abort",
+            Decision::MateDistance => "mate_distance",
+            Decision::Terminal => "terminal",
+            Decision::Repetition => "repetition",
+            Decision::MaxPly => "max_ply",
+            Decision::TTCutoff => "tt_cutoff",
+            Decision::Rfp => "rfp",
+            Decision::Nmp => "nmp",
+            Decision::Probcut => "probcut",
+            Decision::Multicut => "multicut",
+            Decision::StandPat => "stand_pat",
+        }
+    }
+}
+
+/// Why the move loop skipped a move. Some prunings only skip the move itself, while others skip
+/// the remaining quiets or all remaining moves, which the move picker then doesn't even yield, so
+/// they are only recorded at the move that triggered them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Skip {
+    /// The move is skipped because it loses material.
+    TacticSee,
+    /// The move is searched, but all quiets after it are skipped.
+    Lmp,
+    /// The move is searched, but all quiets after it are skipped.
+    Fp,
+    /// The move and all quiets after it are skipped.
+    HistPrune,
+    /// The move is skipped because it loses material.
+    QuietSee,
+    /// The move and all moves after it are skipped.
+    QsLmp,
+    /// The move and all moves after it are skipped.
+    QsSee,
+    /// The move is a quiet evasion, which is skipped along with all quiets after it once the
+    /// node isn't mated.
+    QsQuiet,
+    /// The move is skipped because it can't raise the score above alpha.
+    QsFp,
+}
+
+impl Skip {
+    pub fn name(self) -> &'static str {
+        match self {
+            Skip::TacticSee => "tactic_see",
+            Skip::Lmp => "lmp",
+            Skip::Fp => "fp",
+            Skip::HistPrune => "hist_prune",
+            Skip::QuietSee => "quiet_see",
+            Skip::QsLmp => "qs_lmp",
+            Skip::QsSee => "qs_see",
+            Skip::QsQuiet => "qs_quiet",
+            Skip::QsFp => "qs_fp",
+        }
+    }
+}
+
+#[cfg(feature = "trace")]
+struct Frame {
+    id: u64,
+    parent: Option<u64>,
+    kind: NodeKind,
+    ply: u16,
+    mv: Option<Move>,
+    depth: i32,
+    alpha: Score,
+    beta: Score,
+    tt: Option<TTEntry>,
+    decision: Decision,
+    skipped: Vec<(Move, Skip)>,
+    upcoming_repetition: bool,
+}
+
+#[derive(Default)]
+pub struct Tracer {
+    #[cfg(feature = "trace")]
+    out: Option<BufWriter<File>>,
+    #[cfg(feature = "trace")]
+    max_ply: u16,
+    #[cfg(feature = "trace")]
+    chess960: bool,
+    #[cfg(feature = "trace")]
+    next_id: u64,
+    #[cfg(feature = "trace")]
+    stack: Vec<Frame>,
+}
+
+#[cfg(feature = "trace")]
+impl Tracer {
+    /// Starts a new trace, truncating the configured trace file. Tracing is disabled if no
+    /// path is configured, or if the file can't be created.
+    pub fn start(&mut self, config: &TraceConfig, root_fen: &str, chess960: bool) {
+        self.finish();
+        self.out = config
+            .path
+            .as_ref()
+            .and_then(|path| match File::create(path) {
+                Ok(file) => Some(BufWriter::new(file)),
+                Err(e) => {
+                    println!("info string Failed to create trace file: {e}");
+                    None
+                }
+            });
+        self.max_ply = config.max_ply;
+        self.chess960 = chess960;
+        self.next_id = 0;
+        self.stack.clear();
+
+        if let Some(out) = &mut self.out {
+            let _ = writeln!(out, r#"{{"type":"search","fen":"{root_fen}"}}"#);
+        }
+    }
+
+    /// Flushes and closes the trace file.
+    pub fn finish(&mut self) {
+        if let Some(mut out) = self.out.take() {
+            let _ = out.flush();
+        }
+    }
+
+    fn active(&self, ply: u16) -> bool {
+        self.out.is_some() && ply <= self.max_ply
+    }
+
+    pub fn enter(
+        &mut self,
+        kind: NodeKind,
+        ply: u16,
+        mv: Option<Move>,
+        depth: i32,
+        alpha: Score,
+        beta: Score,
+    ) {
+        if !self.active(ply) {
+            return;
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.stack.push(Frame {
+            id,
+            parent: self.stack.last().map(|f| f.id),
+            kind,
+            ply,
+            mv,
+            depth,
+            alpha,
+            beta,
+            tt: None,
+            decision: Decision::Searched,
+            skipped: vec![],
+            upcoming_repetition: false,
+        });
+    }
+
+    pub fn tt_probe(&mut self, ply: u16, entry: Option<TTEntry>) {
+        if self.active(ply) {
+            self.stack.last_mut().unwrap().tt = entry;
+        }
+    }
+
+    pub fn decide(&mut self, ply: u16, decision: Decision) {
+        if self.active(ply) {
+            self.stack.last_mut().unwrap().decision = decision;
+        }
+    }
+
+    pub fn skip(&mut self, ply: u16, mv: Move, skip: Skip) {
+        if self.active(ply) {
+            self.stack.last_mut().unwrap().skipped.push((mv, skip));
+        }
+    }
+
+    /// Records that alpha was raised to the draw score because of an upcoming repetition.
+    pub fn upcoming_repetition(&mut self, ply: u16) {
+        if self.active(ply) {
+            self.stack.last_mut().unwrap().upcoming_repetition = true;
+        }
+    }
+
+    pub fn exit(&mut self, ply: u16, score: Score) {
+        if !self.active(ply) {
+            return;
+        }
+
+        let frame = self.stack.pop().unwrap();
+        let opt = |s: Option<String>| s.unwrap_or_else(|| "null".to_string());
+
+        let parent = opt(frame.parent.map(|p| p.to_string()));
+        let mv = opt(frame
+            .mv
+            .map(|mv| format!(r#""{}""#, mv.display(self.chess960))));
+        let tt = opt(frame.tt.map(|e| {
+            format!(
+                r#"{{"move":{},"score":{},"eval":{},"depth":{},"flag":"{:?}","pv":{}}}"#,
+                opt(e.mv.map(|mv| format!(r#""{}""#, mv.display(self.chess960)))),
+                e.score.0,
+                e.eval.0,
+                e.depth,
+                e.flags.tt_flag(),
+                e.flags.pv(),
+            )
+        }));
+        let skipped: Vec<String> = frame
+            .skipped
+            .iter()
+            .map(|(mv, skip)| {
+                format!(
+                    r#"{{"move":"{}","reason":"{}"}}"#,
+                    mv.display(self.chess960),
+                    skip.name()
+                )
+            })
+            .collect();
+
+        let out = self.out.as_mut().unwrap();
+        let _ = writeln!(
+            out,
+            r#"{{"type":"node","id":{},"parent":{parent},"kind":"{:?}","ply":{},"move":{mv},"depth":{:.3},"alpha":{},"beta":{},"tt":{tt},"decision":"{}","skipped":[{}],"upcoming_repetition":{},"score":{}}}"#,
+            frame.id,
+            frame.kind,
+            frame.ply,
+            frame.depth as f32 / DEPTH_SCALE as f32,
+            frame.alpha.0,
+            frame.beta.0,
+            frame.decision.name(),
+            skipped.join(","),
+            frame.upcoming_repetition,
+            score.0,
+        );
+    }
+}
+
+#[cfg(not(feature = "trace"))]
+impl Tracer {
+    #[inline(always)]
+    pub fn finish(&mut self) {}
+
+    #[inline(always)]
+    pub fn tt_probe(&mut self, _ply: u16, _entry: Option<TTEntry>) {}
+
+    #[inline(always)]
+    pub fn decide(&mut self, _ply: u16, _decision: Decision) {}
+
+    #[inline(always)]
+    pub fn skip(&mut self, _ply: u16, _mv: Move, _skip: Skip) {}
+
+    #[inline(always)]
+    pub fn upcoming_repetition(&mut self, _ply: u16) {}
+
+    #[inline(always)]
+    pub fn exit(&mut self, _ply: u16, _score: Score) {}
+}
@@ -10,7 +10,7 @@ use crate::score::Score;
 pub const DEFAULT_TT_SIZE: u64 = 16;
 pub const MAX_TT_SIZE: u64 = 1048576;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum TTFlag {
     None = 0,
     Exact = 1,
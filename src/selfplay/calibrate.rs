@@ -0,0 +1,119 @@
+//! The `calibrate` subcommand, which measures the strength of every skill level and prints the
+//! level to Elo table used by `UCI_Elo`. Levels far apart in strength can't be compared directly,
+//! since the weaker one loses every game, so every level instead plays against the level above
+//! it. Full strength is assumed to play at `--elo`, and the Elo of the levels below is the sum of
+//! the measured differences.
+
+use std::time::Instant;
+
+use clap::{Args, Parser};
+use icarus_board::game::GameResult;
+use icarus_common::piece::Color;
+
+use crate::{
+    search::skill::{MAX_ELO, MAX_SKILL_LEVEL, Skill},
+    selfplay::{
+        Adjudication, EngineConfig, MoveLimit, OpeningPicker, Openings, Player, play_game,
+        sprt::Pentanomial,
+    },
+};
+
+#[derive(Parser)]
+enum Cmd {
+    Calibrate(CalibrateArgs),
+}
+
+#[derive(Args)]
+struct CalibrateArgs {
+    /// Number of games per level. Rounded up to an even number, since openings are played twice.
+    #[clap(short, long, default_value_t = 200)]
+    games: usize,
+    /// Soft nodes per move. Levels are restricted further by their own node and depth limits.
+    #[clap(short, long, default_value_t = 20000)]
+    nodes: u64,
+    /// The Elo full strength is assumed to play at.
+    #[clap(short, long, default_value_t = MAX_ELO)]
+    elo: u16,
+    /// Seed for generating random openings.
+    #[clap(long)]
+    seed: Option<u64>,
+}
+
+pub fn calibrate() {
+    let Cmd::Calibrate(args) = Cmd::parse();
+
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let openings = Openings::Random {
+        dfrc: false,
+        random_moves: 8,
+    };
+    let mut picker = OpeningPicker::new(&openings, seed);
+    let adjudication = Adjudication::default();
+    let player = |level| {
+        let config = EngineConfig {
+            skill: Skill {
+                level,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        Player::new(config, false)
+    };
+
+    println!(
+        "Calibrating with {} games per level at {} nodes per move, seed {seed}",
+        2 * args.games.div_ceil(2),
+        args.nodes
+    );
+
+    let mut table = [0.0; MAX_SKILL_LEVEL as usize + 1];
+    table[MAX_SKILL_LEVEL as usize] = args.elo as f64;
+    let mut stronger = player(MAX_SKILL_LEVEL);
+    for level in (0..MAX_SKILL_LEVEL).rev() {
+        let start = Instant::now();
+        let mut weaker = player(level);
+        let mut penta = Pentanomial::default();
+        let (mut wins, mut draws, mut losses) = (0, 0, 0);
+
+        for pair in 0..args.games.div_ceil(2) {
+            let opening = picker.get(pair);
+            let limit = MoveLimit::Nodes(args.nodes);
+            let first = play_game(opening, &mut weaker, &mut stronger, limit, &adjudication);
+            let second = play_game(opening, &mut stronger, &mut weaker, limit, &adjudication);
+
+            let mut half_points = 0;
+            for (game, color) in [first, second].iter().zip([Color::White, Color::Black]) {
+                match game.result().and_then(GameResult::winner) {
+                    Some(winner) if winner == color => {
+                        wins += 1;
+                        half_points += 2;
+                    }
+                    Some(_) => losses += 1,
+                    None => {
+                        draws += 1;
+                        half_points += 1;
+                    }
+                }
+            }
+            penta.add(half_points);
+        }
+
+        let (diff, error) = penta.elo();
+        // Keep the table increasing, so that every Elo maps to a single level.
+        let elo = (table[level as usize + 1] + diff).min(table[level as usize + 1] - 1.0);
+        table[level as usize] = elo;
+        println!(
+            "Level {level:>2} vs {:>2}: W: {wins:>4} D: {draws:>4} L: {losses:>4} Elo: {diff:>7.2} +/- {error:<6.2} -> {elo:>4.0}, took {:.1?}",
+            level + 1,
+            start.elapsed(),
+        );
+
+        stronger = weaker;
+    }
+
+    println!("pub const LEVEL_ELO: [u16; MAX_SKILL_LEVEL as usize + 1] = [");
+    for (level, elo) in table.iter().enumerate() {
+        println!("    {:.0}, // {level}", elo.max(0.0));
+    }
+    println!("];");
+}
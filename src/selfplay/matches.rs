@@ -0,0 +1,275 @@
+//! The `match` subcommand, which plays games between two engine configurations and reports the
+//! Elo difference, LOS and SPRT status of the first configuration against the second one.
+
+use std::{
+    num::NonZero,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering::Relaxed},
+        mpsc::{Sender, channel},
+    },
+    time::Instant,
+};
+
+use clap::{Args, CommandFactory, Parser, error::ErrorKind};
+use icarus_board::game::{GameResult, WinReason};
+use icarus_common::piece::Color;
+
+use crate::selfplay::{
+    Adjudication, Clock, EngineConfig, MoveLimit, OpeningPicker, Openings, Player, play_game,
+    sprt::{Pentanomial, Sprt},
+};
+
+#[derive(Parser)]
+enum Cmd {
+    Match(MatchArgs),
+}
+
+#[derive(Args)]
+struct MatchArgs {
+    /// Engine configuration as comma separated `key=value` pairs, e.g.
+    /// `name=dev,net=dev.nnue,hash=16`. Supported keys are `name`, `net`, `hash`, `contempt`,
+    /// `skill`, `elo`, `params` and the names of tunable parameters. Must be given exactly twice; results are from the first engine's
+    /// perspective.
+    #[clap(short, long = "engine", required = true)]
+    engines: Vec<EngineConfig>,
+    #[clap(flatten)]
+    limit: LimitArgs,
+    /// Maximum number of games. Rounded up to an even number, since openings are played twice.
+    #[clap(short, long, default_value_t = 1000)]
+    games: usize,
+    /// Number of games played in parallel. Defaults to `available_parallelism()`.
+    #[clap(short, long)]
+    concurrency: Option<usize>,
+    /// FEN or EPD file with openings. If none is given, random openings are generated.
+    #[clap(short, long)]
+    book: Option<PathBuf>,
+    /// Seed for shuffling the book and generating random openings.
+    #[clap(long)]
+    seed: Option<u64>,
+    /// Number of random plies played in generated openings.
+    #[clap(long, default_value_t = 8)]
+    random_moves: usize,
+    /// Play (Double) Fischer Random chess.
+    #[clap(short, long)]
+    dfrc: bool,
+
+    #[clap(long, default_value_t = 0.0)]
+    elo0: f64,
+    #[clap(long, default_value_t = 5.0)]
+    elo1: f64,
+    #[clap(long, default_value_t = 0.05)]
+    alpha: f64,
+    #[clap(long, default_value_t = 0.05)]
+    beta: f64,
+    /// Keep playing until `games` is reached, instead of stopping when the SPRT finishes.
+    #[clap(long)]
+    no_sprt: bool,
+
+    #[clap(long, default_value_t = 40)]
+    draw_movenumber: usize,
+    #[clap(long, default_value_t = 8)]
+    draw_movecount: usize,
+    #[clap(long, default_value_t = 10)]
+    draw_score: i16,
+    #[clap(long, default_value_t = 3)]
+    resign_movecount: usize,
+    #[clap(long, default_value_t = 400)]
+    resign_score: i16,
+}
+
+#[derive(Args, Debug)]
+#[group(required = true, multiple = false)]
+struct LimitArgs {
+    /// Time control in seconds, e.g. `8+0.08`.
+    #[clap(long)]
+    tc: Option<Clock>,
+    /// Soft nodes per move.
+    #[clap(short, long)]
+    nodes: Option<u64>,
+}
+
+struct MatchCtx {
+    engines: [EngineConfig; 2],
+    openings: Openings,
+    limit: MoveLimit,
+    adjudication: Adjudication,
+    chess960: bool,
+    seed: u64,
+    pairs: usize,
+    next_pair: AtomicUsize,
+    stop: AtomicBool,
+}
+
+/// The results of an opening played with both colors, from the first engine's perspective.
+struct PairResult {
+    games: [GameResult; 2],
+}
+
+impl PairResult {
+    fn half_points(result: GameResult, color: Color) -> usize {
+        match result.winner() {
+            Some(winner) if winner == color => 2,
+            Some(_) => 0,
+            None => 1,
+        }
+    }
+
+    /// The first engine plays white in the first game and black in the second.
+    fn pair_half_points(&self) -> usize {
+        Self::half_points(self.games[0], Color::White)
+            + Self::half_points(self.games[1], Color::Black)
+    }
+}
+
+pub fn run_match() {
+    let Cmd::Match(args) = Cmd::parse();
+
+    let Ok(engines) = <[EngineConfig; 2]>::try_from(args.engines) else {
+        Cmd::command()
+            .error(
+                ErrorKind::WrongNumberOfValues,
+                "`--engine` must be given exactly twice",
+            )
+            .exit();
+    };
+
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let openings = match Openings::new(args.book.as_deref(), args.dfrc, args.random_moves, seed) {
+        Ok(openings) => openings,
+        Err(e) => Cmd::command().error(ErrorKind::Io, e).exit(),
+    };
+
+    let limit = match (args.limit.tc, args.limit.nodes) {
+        (Some(clock), _) => MoveLimit::Clock(clock),
+        (_, Some(nodes)) => MoveLimit::Nodes(nodes),
+        _ => unreachable!("clap requires a limit"),
+    };
+
+    let ctx = MatchCtx {
+        engines,
+        openings,
+        limit,
+        adjudication: Adjudication {
+            draw_movenumber: args.draw_movenumber,
+            draw_movecount: args.draw_movecount,
+            draw_score: args.draw_score,
+            resign_movecount: args.resign_movecount,
+            resign_score: args.resign_score,
+        },
+        chess960: args.dfrc,
+        seed,
+        pairs: args.games.div_ceil(2),
+        next_pair: AtomicUsize::new(0),
+        stop: AtomicBool::new(false),
+    };
+
+    let sprt = (!args.no_sprt).then_some(Sprt {
+        elo0: args.elo0,
+        elo1: args.elo1,
+        alpha: args.alpha,
+        beta: args.beta,
+    });
+    let concurrency = args
+        .concurrency
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, NonZero::get));
+
+    let [a, b] = &ctx.engines;
+    println!(
+        "{} vs {}: {} games, {} threads, seed {}",
+        a.name,
+        b.name,
+        2 * ctx.pairs,
+        concurrency,
+        ctx.seed
+    );
+
+    let (tx, rx) = channel();
+    let start = Instant::now();
+
+    std::thread::scope(|s| {
+        for worker in 0..concurrency {
+            let tx = tx.clone();
+            let ctx = &ctx;
+            s.spawn(move || worker_loop(ctx, worker, tx));
+        }
+        drop(tx);
+
+        let mut penta = Pentanomial::default();
+        let (mut wins, mut draws, mut losses, mut timeouts) = (0, 0, 0, 0);
+
+        for pair in rx.iter() {
+            penta.add(pair.pair_half_points());
+            for (result, color) in pair.games.into_iter().zip([Color::White, Color::Black]) {
+                match PairResult::half_points(result, color) {
+                    2 => wins += 1,
+                    1 => draws += 1,
+                    _ => losses += 1,
+                }
+                if matches!(result, GameResult::Win(_, WinReason::Timeout)) {
+                    timeouts += 1;
+                }
+            }
+
+            let (elo, error) = penta.elo();
+            let mut line = format!(
+                "Games: {:>6} W: {wins:>5} D: {draws:>5} L: {losses:>5} Elo: {elo:>7.2} +/- {error:<6.2} LOS: {:>6.2}%",
+                wins + draws + losses,
+                100.0 * penta.los(),
+            );
+
+            let mut decision = None;
+            if let Some(sprt) = &sprt {
+                let llr = penta.llr(sprt.elo0, sprt.elo1);
+                let (lower, upper) = sprt.bounds();
+                line += &format!(" LLR: {llr:.2} ({lower:.2}, {upper:.2})");
+                decision = sprt.decision(llr);
+            }
+            println!("{line}");
+
+            if let Some(h1) = decision {
+                println!(
+                    "SPRT [{}, {}] finished: {} accepted",
+                    sprt.unwrap().elo0,
+                    sprt.unwrap().elo1,
+                    if h1 { "H1" } else { "H0" },
+                );
+                ctx.stop.store(true, Relaxed);
+                break;
+            }
+        }
+
+        println!(
+            "Pentanomial [0, 0.5, 1, 1.5, 2]: {:?}, time losses: {timeouts}, took {:.1?}",
+            penta.counts,
+            start.elapsed(),
+        );
+    });
+}
+
+fn worker_loop(ctx: &MatchCtx, worker: usize, tx: Sender<PairResult>) {
+    let mut players = ctx
+        .engines
+        .clone()
+        .map(|config| Player::new(config, ctx.chess960));
+    let mut picker = OpeningPicker::new(&ctx.openings, ctx.seed.wrapping_add(worker as u64));
+
+    while !ctx.stop.load(Relaxed) {
+        let pair = ctx.next_pair.fetch_add(1, Relaxed);
+        if pair >= ctx.pairs {
+            break;
+        }
+
+        let opening = picker.get(pair);
+        let [a, b] = &mut players;
+        let first = play_game(opening, a, b, ctx.limit, &ctx.adjudication);
+        let second = play_game(opening, b, a, ctx.limit, &ctx.adjudication);
+
+        let result = PairResult {
+            games: [first, second].map(|game| game.result().unwrap()),
+        };
+        if tx.send(result).is_err() {
+            break;
+        }
+    }
+}
@@ -0,0 +1,431 @@
+//! In-process games between engine configurations. Every configuration gets its own
+//! `ThreadCtx` with a private transposition table, and games are played without going through
+//! UCI, so no external match runner or engine binaries are needed.
+
+pub mod calibrate;
+pub mod matches;
+pub mod sprt;
+pub mod spsa;
+
+use std::{
+    path::Path,
+    str::FromStr,
+    sync::{Arc, atomic::Ordering::Relaxed},
+    time::Instant,
+};
+
+use icarus_board::{
+    board::Board,
+    game::{DrawReason, Game, GameResult, WinReason},
+    r#move::Move,
+};
+use icarus_common::{
+    piece::Color,
+    util::enum_map::{EnumMap, enum_map},
+};
+use rand::{SeedableRng, rngs::SmallRng, seq::SliceRandom};
+
+use crate::{
+    bench::read_positions,
+    datagen::genfens::try_generate_pos,
+    nnue::network::{NET, Network},
+    position::Position,
+    score::Score,
+    search::{
+        params::Params,
+        searcher::{GlobalCtx, Print, SearchParams, ThreadCtx},
+        skill::{MAX_SKILL_LEVEL, Skill},
+        transposition_table::{DEFAULT_TT_SIZE, MAX_TT_SIZE, TTable},
+    },
+    uci::SearchLimit,
+    wdl,
+};
+
+/// Time reserved per move when playing with a clock. Matches the `MoveOverhead` used by the
+/// fastchess scripts in `sprt/`.
+const MOVE_OVERHEAD: u64 = 10;
+
+/// The settings of one engine taking part in self-play games.
+#[derive(Clone)]
+pub struct EngineConfig {
+    pub name: String,
+    pub net: &'static Network,
+    /// Transposition table size in MiB.
+    pub hash: u64,
+    /// Contempt in centipawns.
+    pub contempt: Score,
+    pub skill: Skill,
+    pub params: Params,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            name: "icarus".to_string(),
+            net: *NET,
+            hash: DEFAULT_TT_SIZE,
+            contempt: Score::ZERO,
+            skill: Skill::default(),
+            params: Params::DEFAULT,
+        }
+    }
+}
+
+impl FromStr for EngineConfig {
+    type Err = String;
+
+    /// Parses a comma separated list of `key=value` pairs. The supported keys are `name`,
+    /// `net`, `hash`, `contempt`, `skill` (the skill level) and `elo` (limits the strength to
+    /// the given Elo), `params` (a parameter file, see `Params::load`) and the names of single
+    /// tunable parameters. Keys that aren't given keep their default value.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = Self::default();
+
+        for pair in s.split(',').filter(|p| !p.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("Expected `key=value`, found `{pair}`"))?;
+            let invalid = || format!("Invalid value `{value}` for `{key}`");
+
+            match key.to_ascii_lowercase().as_str() {
+                "name" => config.name = value.to_string(),
+                "net" => {
+                    config.net = Network::load(Path::new(value))
+                        .map(Box::leak)
+                        .map_err(|e| format!("Failed to load net `{value}`: {e}"))?
+                }
+                "hash" => {
+                    config.hash = value
+                        .parse()
+                        .ok()
+                        .filter(|hash| (1..=MAX_TT_SIZE).contains(hash))
+                        .ok_or_else(invalid)?
+                }
+                "contempt" => config.contempt = Score(value.parse().map_err(|_| invalid())?),
+                "skill" => {
+                    config.skill.level = value
+                        .parse()
+                        .ok()
+                        .filter(|&level| level <= MAX_SKILL_LEVEL)
+                        .ok_or_else(invalid)?
+                }
+                "elo" => {
+                    config.skill.limit_strength = true;
+                    config.skill.elo = value.parse().map_err(|_| invalid())?;
+                }
+                "params" => config.params = Params::load(Path::new(value))?,
+                name if config.params.get(name).is_some() => {
+                    let val = value.parse().map_err(|_| invalid())?;
+                    if !config.params.set(name, val) {
+                        return Err(invalid());
+                    }
+                }
+                _ => return Err(format!("Unknown engine option `{key}`")),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// A time control of `base` milliseconds plus `inc` milliseconds per move.
+#[derive(Clone, Copy, Debug)]
+pub struct Clock {
+    pub base: u64,
+    pub inc: u64,
+}
+
+impl FromStr for Clock {
+    type Err = String;
+
+    /// Parses a time control like `8+0.08`, given in seconds.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (base, inc) = s.split_once('+').unwrap_or((s, "0"));
+        let millis = |s: &str| {
+            s.parse::<f64>()
+                .ok()
+                .filter(|t| t.is_finite() && *t >= 0.0)
+                .map(|t| (t * 1000.0).round() as u64)
+                .ok_or_else(|| format!("Invalid time control `{s}`"))
+        };
+
+        Ok(Self {
+            base: millis(base)?,
+            inc: millis(inc)?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum MoveLimit {
+    /// Soft node limit per move.
+    Nodes(u64),
+    Clock(Clock),
+}
+
+/// Ends games early once the scores of both engines agree that they are decided. Scores are
+/// counted per ply, so both engines have to report them, and are in normalized centipawns like
+/// the `cp` scores in the UCI output.
+#[derive(Clone, Copy, Debug)]
+pub struct Adjudication {
+    /// Draw adjudication only starts at this full move number.
+    pub draw_movenumber: usize,
+    pub draw_movecount: usize,
+    pub draw_score: i16,
+    pub resign_movecount: usize,
+    pub resign_score: i16,
+}
+
+pub enum Openings {
+    Book(Vec<Board>),
+    Random { dfrc: bool, random_moves: usize },
+}
+
+impl Openings {
+    /// Reads the openings from `book` and shuffles them with `seed`, or generates random
+    /// openings if no book is given.
+    pub fn new(
+        book: Option<&Path>,
+        dfrc: bool,
+        random_moves: usize,
+        seed: u64,
+    ) -> Result<Self, String> {
+        Ok(match book {
+            Some(path) => {
+                let mut book = read_positions(path)?;
+                book.shuffle(&mut SmallRng::seed_from_u64(seed));
+                Openings::Book(book)
+            }
+            None => Openings::Random { dfrc, random_moves },
+        })
+    }
+}
+
+/// Hands out openings to a single worker thread.
+pub struct OpeningPicker<'a> {
+    openings: &'a Openings,
+    rng: SmallRng,
+    /// Only used to verify that generated openings are reasonably balanced.
+    threads: Option<EnumMap<Color, ThreadCtx>>,
+}
+
+impl<'a> OpeningPicker<'a> {
+    pub fn new(openings: &'a Openings, seed: u64) -> Self {
+        Self {
+            openings,
+            rng: SmallRng::seed_from_u64(seed),
+            threads: None,
+        }
+    }
+
+    /// Returns the `idx`th book opening, or a new random opening.
+    pub fn get(&mut self, idx: usize) -> Board {
+        match self.openings {
+            Openings::Book(book) => book[idx % book.len()],
+            &Openings::Random { dfrc, random_moves } => {
+                let threads = self.threads.get_or_insert_with(|| {
+                    enum_map! { _ => {
+                        let config = EngineConfig {
+                            hash: 1,
+                            ..Default::default()
+                        };
+                        Player::new(config, dfrc).thread
+                    }}
+                });
+                std::iter::repeat_with(|| {
+                    try_generate_pos(&mut self.rng, dfrc, random_moves, threads)
+                })
+                .flatten()
+                .next()
+                .unwrap()
+            }
+        }
+    }
+}
+
+impl Default for Adjudication {
+    /// The settings used by the fastchess scripts in `sprt/`.
+    fn default() -> Self {
+        Self {
+            draw_movenumber: 40,
+            draw_movecount: 8,
+            draw_score: 10,
+            resign_movecount: 3,
+            resign_score: 400,
+        }
+    }
+}
+
+/// One engine playing games, along with the thread it searches on.
+pub struct Player {
+    pub config: EngineConfig,
+    thread: ThreadCtx,
+}
+
+impl Player {
+    pub fn new(config: EngineConfig, chess960: bool) -> Self {
+        let global = Arc::new(GlobalCtx {
+            time_manager: Default::default(),
+            nodes: Default::default(),
+            num_searching: Default::default(),
+            ttable: TTable::new(config.hash),
+            stats: Default::default(),
+            turnstile: Default::default(),
+            results: Default::default(),
+            last_result: Default::default(),
+        });
+        let mut thread = ThreadCtx::new(global, 0, chess960, true);
+        thread.nnue.set_net(config.net);
+
+        Self { config, thread }
+    }
+
+    fn newgame(&mut self) {
+        self.thread.history.clear();
+        self.thread.global.ttable.clear();
+    }
+
+    /// Searches `pos` and returns the best move, along with its score from the side to move's
+    /// perspective.
+    fn search(
+        &mut self,
+        pos: &Position,
+        limits: &[SearchLimit],
+        soft_nodes: bool,
+    ) -> (Move, Score) {
+        let thread = &mut self.thread;
+        let skill = self.config.skill;
+
+        thread.global.nodes.store(0, Relaxed);
+        thread.global.num_searching.store(1, Relaxed);
+        thread.global.time_manager.init(
+            pos.board().stm(),
+            limits,
+            soft_nodes,
+            true,
+            MOVE_OVERHEAD,
+            0,
+            &self.config.params,
+        );
+        if skill.enabled() {
+            thread
+                .global
+                .time_manager
+                .restrict(skill.max_depth(), skill.max_nodes());
+        }
+
+        let score = thread.do_search(SearchParams {
+            pos: pos.clone(),
+            root_moves: None,
+            chess960: thread.chess960,
+            print_info: Print::None,
+            debug: false,
+            trace: Default::default(),
+            deterministic: false,
+            skill,
+            contempt: self.config.contempt,
+            wdl_contempt: Score::ZERO,
+            params: self.config.params,
+        });
+
+        (thread.root_pv[0], score)
+    }
+}
+
+/// Plays a game from `opening`, and returns the game with its result set. Claimable draws are
+/// always claimed.
+pub fn play_game(
+    opening: Board,
+    white: &mut Player,
+    black: &mut Player,
+    limit: MoveLimit,
+    adjudication: &Adjudication,
+) -> Game {
+    let mut pos = Position::new(opening);
+    let mut game = Game::new(opening);
+    white.newgame();
+    black.newgame();
+
+    let mut clocks = enum_map! { _ => match limit {
+        MoveLimit::Clock(clock) => clock.base,
+        MoveLimit::Nodes(_) => 0,
+    }};
+    let mut draw_count = 0;
+    let mut resign_count = 0;
+    let mut prev_score: Option<Score> = None;
+
+    while !game.is_over() {
+        let stm = pos.board().stm();
+        let player = match stm {
+            Color::White => &mut *white,
+            Color::Black => &mut *black,
+        };
+
+        let start = Instant::now();
+        let (mv, score) = match limit {
+            MoveLimit::Nodes(nodes) => player.search(&pos, &[SearchLimit::Nodes(nodes)], true),
+            MoveLimit::Clock(clock) => player.search(
+                &pos,
+                &[
+                    SearchLimit::WhiteTime(clocks[Color::White]),
+                    SearchLimit::BlackTime(clocks[Color::Black]),
+                    SearchLimit::WhiteInc(clock.inc),
+                    SearchLimit::BlackInc(clock.inc),
+                ],
+                false,
+            ),
+        };
+
+        if let MoveLimit::Clock(clock) = limit {
+            let elapsed = start.elapsed().as_millis() as u64;
+            if elapsed > clocks[stm] {
+                game.adjudicate(GameResult::Win(!stm, WinReason::Timeout));
+                break;
+            }
+            clocks[stm] = clocks[stm] - elapsed + clock.inc;
+        }
+
+        // Adjudicate on the normalized score that fastchess sees in the UCI output.
+        let material = pos.board().classical_material();
+        pos.make_move(mv, None);
+        if game.make_move(mv).is_some() {
+            break;
+        }
+
+        let white_score = wdl::normalize(score, material) * stm.signum() as i16;
+        if let Some(prev) = prev_score {
+            if white_score.0.abs() >= adjudication.resign_score
+                && white_score.0.signum() == prev.0.signum()
+            {
+                resign_count += 1;
+            } else {
+                resign_count = 0;
+            }
+        }
+
+        if game.moves().len().div_ceil(2) >= adjudication.draw_movenumber
+            && white_score.0.abs() <= adjudication.draw_score
+        {
+            draw_count += 1;
+        } else {
+            draw_count = 0;
+        }
+
+        prev_score = Some(white_score);
+
+        if resign_count >= adjudication.resign_movecount * 2 {
+            let winner = if white_score.0 > 0 {
+                Color::White
+            } else {
+                Color::Black
+            };
+            game.adjudicate(GameResult::Win(winner, WinReason::Adjudication));
+        } else if draw_count >= adjudication.draw_movecount * 2 {
+            game.adjudicate(GameResult::Draw(DrawReason::Adjudication));
+        } else {
+            game.claim_draw();
+        }
+    }
+
+    game
+}
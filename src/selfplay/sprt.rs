@@ -0,0 +1,191 @@
+//! Match statistics. Games are played in pairs with swapped colors, so results are tracked as
+//! a pentanomial distribution over the score of a game pair, which accounts for the correlation
+//! between the two games of a pair. The SPRT uses the normalized GSPRT approximation of the
+//! log-likelihood ratio, like fastchess and OpenBench.
+
+/// Number of game pairs per pair score, in half points of the first engine: `counts[0]` is a
+/// pair lost twice, `counts[4]` a pair won twice.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Pentanomial {
+    pub counts: [u64; 5],
+}
+
+impl Pentanomial {
+    pub fn add(&mut self, half_points: usize) {
+        self.counts[half_points] += 1;
+    }
+
+    pub fn pairs(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Mean and variance of the per-pair score, normalized to `0..=1`.
+    fn mean_variance(&self) -> (f64, f64) {
+        let n = self.pairs() as f64;
+        let score = |i: usize| i as f64 / 4.0;
+
+        let mean = (0..5)
+            .map(|i| self.counts[i] as f64 * score(i))
+            .sum::<f64>()
+            / n;
+        let variance = (0..5)
+            .map(|i| self.counts[i] as f64 * (score(i) - mean).powi(2))
+            .sum::<f64>()
+            / n;
+
+        (mean, variance)
+    }
+
+    /// The Elo difference, along with the half width of its 95% confidence interval.
+    pub fn elo(&self) -> (f64, f64) {
+        if self.pairs() == 0 {
+            return (0.0, 0.0);
+        }
+
+        let (mean, variance) = self.mean_variance();
+        let margin = 1.96 * (variance / self.pairs() as f64).sqrt();
+
+        let elo = score_to_elo(mean);
+        let error = (score_to_elo(mean + margin) - score_to_elo(mean - margin)) / 2.0;
+        (elo, error)
+    }
+
+    /// Likelihood of superiority: the probability that the first engine is stronger.
+    pub fn los(&self) -> f64 {
+        if self.pairs() == 0 {
+            return 0.5;
+        }
+
+        let (mean, variance) = self.mean_variance();
+        // If all pairs had the same result, the LOS is either 0, 0.5 or 1.
+        let stderr = (variance / self.pairs() as f64).sqrt().max(f64::EPSILON);
+        0.5 * (1.0 + erf((mean - 0.5) / stderr / std::f64::consts::SQRT_2))
+    }
+
+    /// Log-likelihood ratio of H1 (the Elo difference is `elo1`) against H0 (it is `elo0`).
+    pub fn llr(&self, elo0: f64, elo1: f64) -> f64 {
+        if self.pairs() == 0 {
+            return 0.0;
+        }
+
+        let (mean, variance) = self.mean_variance();
+        if variance == 0.0 {
+            return 0.0;
+        }
+
+        let (s0, s1) = (elo_to_score(elo0), elo_to_score(elo1));
+        self.pairs() as f64 * (s1 - s0) * (2.0 * mean - s0 - s1) / (2.0 * variance)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    /// The lower and upper LLR bounds, at which H0 and H1 are accepted respectively.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// Returns `Some(true)` if H1 is accepted, `Some(false)` if H0 is accepted, and `None` if
+    /// the test isn't finished yet.
+    pub fn decision(&self, llr: f64) -> Option<bool> {
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            Some(true)
+        } else if llr <= lower {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+fn elo_to_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+fn score_to_elo(score: f64) -> f64 {
+    let score = score.clamp(0.001, 0.999);
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// Abramowitz and Stegun approximation 7.1.26, with a maximum error of 1.5e-7.
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    (1.0 - poly * (-x * x).exp()).copysign(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::selfplay::sprt::{Pentanomial, Sprt};
+
+    /// A match with 255 game pairs that the first engine won by a bit.
+    const COUNTS: [u64; 5] = [10, 50, 120, 60, 15];
+
+    // Values computed with the logistic pentanomial formulas of fastchess.
+    #[test]
+    fn known_values() {
+        let penta = Pentanomial { counts: COUNTS };
+
+        let (elo, error) = penta.elo();
+        assert!((elo - 13.6319).abs() < 1e-3, "{elo}");
+        assert!((error - 19.3268).abs() < 1e-3, "{error}");
+        assert!((penta.los() - 0.917022).abs() < 1e-5, "{}", penta.los());
+        assert!((penta.llr(0.0, 5.0) - 0.575006).abs() < 1e-5);
+    }
+
+    #[test]
+    fn bounds() {
+        let sprt = Sprt {
+            elo0: 0.0,
+            elo1: 5.0,
+            alpha: 0.05,
+            beta: 0.05,
+        };
+        let (lower, upper) = sprt.bounds();
+        assert!((lower + 2.944439).abs() < 1e-6);
+        assert!((upper - 2.944439).abs() < 1e-6);
+
+        assert_eq!(sprt.decision(3.0), Some(true));
+        assert_eq!(sprt.decision(-3.0), Some(false));
+        assert_eq!(sprt.decision(0.0), None);
+    }
+
+    #[test]
+    fn llr_sign() {
+        let penta = Pentanomial { counts: COUNTS };
+        let (elo, _) = penta.elo();
+
+        // The LLR favors whichever hypothesis the measured Elo is at.
+        assert!(penta.llr(elo, elo + 5.0) < 0.0);
+        assert!(penta.llr(elo - 5.0, elo) > 0.0);
+    }
+
+    #[test]
+    fn symmetric_results() {
+        let penta = Pentanomial {
+            counts: [15, 50, 120, 50, 15],
+        };
+
+        assert!(penta.elo().0.abs() < 1e-9);
+        assert!((penta.los() - 0.5).abs() < 1e-9);
+        assert!(penta.llr(0.0, 5.0) < 0.0);
+
+        let empty = Pentanomial::default();
+        assert_eq!(empty.elo(), (0.0, 0.0));
+        assert_eq!(empty.los(), 0.5);
+        assert_eq!(empty.llr(0.0, 5.0), 0.0);
+    }
+}
@@ -0,0 +1,309 @@
+//! The `spsa` subcommand, a local replacement for OpenBench SPSA tunes. Every iteration plays a
+//! few game pairs at fixed nodes between two perturbations of the current parameters, and moves
+//! the parameters towards the better one. The schedule and its `c_end`/`r_end` settings follow
+//! OpenBench, with `c_end` being a twentieth of each parameter's range like in the configs
+//! printed by `params`.
+//!
+//! Progress is checkpointed after every iteration, and a tune is resumed from its checkpoint
+//! if the output file already exists. Iterations are played concurrently, and each one updates
+//! the parameters as soon as it's done, like OpenBench workers do.
+
+use std::{
+    fmt::Write as _,
+    fs,
+    num::NonZero,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering::Relaxed},
+    },
+    time::Instant,
+};
+
+use clap::{Args, CommandFactory, Parser, error::ErrorKind};
+use icarus_common::piece::Color;
+use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+use crate::{
+    search::params::Params,
+    selfplay::{Adjudication, EngineConfig, MoveLimit, OpeningPicker, Openings, Player, play_game},
+    util::tunable::ParamSpec,
+};
+
+const ALPHA: f64 = 0.602;
+const GAMMA: f64 = 0.101;
+
+#[derive(Parser)]
+enum Cmd {
+    Spsa(SpsaArgs),
+}
+
+#[derive(Args)]
+struct SpsaArgs {
+    /// Checkpoint file. If it already exists, the tune is resumed from it.
+    #[clap(short, long, required = true)]
+    output: PathBuf,
+    /// Total number of iterations.
+    #[clap(short, long, default_value_t = 10000)]
+    iterations: usize,
+    /// Game pairs played per iteration.
+    #[clap(short, long, default_value_t = 4)]
+    pairs: usize,
+    /// Soft nodes per move.
+    #[clap(short, long, default_value_t = 5000)]
+    nodes: u64,
+    /// Number of iterations played in parallel. Defaults to `available_parallelism()`.
+    #[clap(short, long)]
+    concurrency: Option<usize>,
+    /// Comma separated names of the parameters to tune. Defaults to all of them.
+    #[clap(long, value_delimiter = ',')]
+    params: Vec<String>,
+    /// Learning rate at the end of the tune.
+    #[clap(long, default_value_t = 0.002)]
+    r_end: f64,
+    /// FEN or EPD file with openings. If none is given, random openings are generated.
+    #[clap(short, long)]
+    book: Option<PathBuf>,
+    #[clap(long)]
+    seed: Option<u64>,
+    #[clap(long, default_value_t = 8)]
+    random_moves: usize,
+    #[clap(short, long)]
+    dfrc: bool,
+}
+
+/// A tuned parameter, with its OpenBench style schedule.
+struct Tuned {
+    spec: ParamSpec,
+    /// Perturbation size of the first iteration.
+    c: f64,
+    /// Learning rate of the first iteration.
+    a: f64,
+}
+
+struct SpsaState {
+    /// Current values of all tuned parameters, in the order of `SpsaCtx::tuned`.
+    theta: Vec<f64>,
+    /// Number of finished iterations.
+    done: usize,
+}
+
+struct SpsaCtx {
+    tuned: Vec<Tuned>,
+    iterations: usize,
+    /// Stability constant of the learning rate schedule.
+    big_a: f64,
+    pairs: usize,
+    nodes: u64,
+    openings: Openings,
+    chess960: bool,
+    seed: u64,
+    output: PathBuf,
+    next_iteration: AtomicUsize,
+    state: Mutex<SpsaState>,
+    start: Instant,
+}
+
+pub fn run_spsa() {
+    let Cmd::Spsa(args) = Cmd::parse();
+    let fail = |e: String| -> ! { Cmd::command().error(ErrorKind::InvalidValue, e).exit() };
+
+    let specs: Vec<ParamSpec> = if args.params.is_empty() {
+        Params::SPECS.to_vec()
+    } else {
+        args.params
+            .iter()
+            .map(|name| {
+                Params::SPECS
+                    .iter()
+                    .find(|spec| spec.name == name)
+                    .copied()
+                    .unwrap_or_else(|| fail(format!("Unknown parameter `{name}`")))
+            })
+            .collect()
+    };
+
+    let n = args.iterations as f64;
+    let big_a = 0.1 * n;
+    let tuned: Vec<Tuned> = specs
+        .into_iter()
+        .map(|spec| {
+            let c_end = (spec.max - spec.min) / 20.0;
+            let a_end = args.r_end * c_end * c_end;
+            Tuned {
+                spec,
+                c: c_end * n.powf(GAMMA),
+                a: a_end * (big_a + n).powf(ALPHA),
+            }
+        })
+        .collect();
+
+    let mut state = SpsaState {
+        theta: tuned
+            .iter()
+            .map(|t| Params::DEFAULT.get(t.spec.name).unwrap())
+            .collect(),
+        done: 0,
+    };
+    if args.output.exists() {
+        read_checkpoint(&args.output, &tuned, &mut state).unwrap_or_else(|e| fail(e));
+        println!("Resuming from iteration {}", state.done);
+    }
+
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let openings = Openings::new(args.book.as_deref(), args.dfrc, args.random_moves, seed)
+        .unwrap_or_else(|e| fail(e));
+    let concurrency = args
+        .concurrency
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, NonZero::get));
+
+    let ctx = SpsaCtx {
+        tuned,
+        iterations: args.iterations,
+        big_a,
+        pairs: args.pairs,
+        nodes: args.nodes,
+        openings,
+        chess960: args.dfrc,
+        seed,
+        output: args.output,
+        next_iteration: AtomicUsize::new(state.done),
+        state: Mutex::new(state),
+        start: Instant::now(),
+    };
+
+    println!(
+        "Tuning {} parameters for {} iterations of {} game pairs at {} nodes, {} threads, seed {}",
+        ctx.tuned.len(),
+        ctx.iterations,
+        ctx.pairs,
+        ctx.nodes,
+        concurrency,
+        ctx.seed,
+    );
+
+    std::thread::scope(|s| {
+        for worker in 0..concurrency {
+            let ctx = &ctx;
+            s.spawn(move || worker_loop(ctx, worker));
+        }
+    });
+
+    let state = ctx.state.into_inner().unwrap();
+    println!("Final parameters:");
+    for (t, &value) in ctx.tuned.iter().zip(&state.theta) {
+        println!("{} {}", t.spec.name, value.round());
+    }
+}
+
+fn worker_loop(ctx: &SpsaCtx, worker: usize) {
+    let seed = ctx.seed.wrapping_add(worker as u64);
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let mut picker = OpeningPicker::new(&ctx.openings, seed);
+    let mut players = [(); 2].map(|_| Player::new(EngineConfig::default(), ctx.chess960));
+    let adjudication = Adjudication::default();
+
+    loop {
+        let k = ctx.next_iteration.fetch_add(1, Relaxed);
+        if k >= ctx.iterations {
+            break;
+        }
+
+        let c_k: Vec<f64> = ctx
+            .tuned
+            .iter()
+            .map(|t| t.c / (k as f64 + 1.0).powf(GAMMA))
+            .collect();
+        let delta: Vec<f64> = ctx
+            .tuned
+            .iter()
+            .map(|_| if rng.random() { 1.0 } else { -1.0 })
+            .collect();
+
+        let theta = ctx.state.lock().unwrap().theta.clone();
+        for (player, sign) in players.iter_mut().zip([1.0, -1.0]) {
+            let mut params = Params::DEFAULT;
+            for (i, t) in ctx.tuned.iter().enumerate() {
+                let value = (theta[i] + sign * c_k[i] * delta[i]).clamp(t.spec.min, t.spec.max);
+                params.set(t.spec.name, value);
+            }
+            player.config.params = params;
+        }
+
+        // Wins minus losses of the positive perturbation.
+        let mut result = 0i32;
+        for pair in 0..ctx.pairs {
+            let opening = picker.get(k * ctx.pairs + pair);
+            let [plus, minus] = &mut players;
+            let limit = MoveLimit::Nodes(ctx.nodes);
+
+            let first = play_game(opening, plus, minus, limit, &adjudication);
+            let second = play_game(opening, minus, plus, limit, &adjudication);
+            for (game, plus_color) in [(first, Color::White), (second, Color::Black)] {
+                match game.result().unwrap().winner() {
+                    Some(winner) if winner == plus_color => result += 1,
+                    Some(_) => result -= 1,
+                    None => {}
+                }
+            }
+        }
+
+        let mut state = ctx.state.lock().unwrap();
+        for (i, t) in ctx.tuned.iter().enumerate() {
+            let a_k = t.a / (ctx.big_a + k as f64 + 1.0).powf(ALPHA);
+            state.theta[i] = (state.theta[i] + a_k / c_k[i] * result as f64 * delta[i])
+                .clamp(t.spec.min, t.spec.max);
+        }
+        state.done += 1;
+
+        if let Err(e) = write_checkpoint(&ctx.output, &ctx.tuned, &state) {
+            println!("Failed to write checkpoint: {e}");
+        }
+        println!(
+            "Iteration {:>6}/{}: result {result:+3}, elapsed {:.1?}",
+            state.done,
+            ctx.iterations,
+            ctx.start.elapsed(),
+        );
+    }
+}
+
+/// Writes the number of finished iterations as a comment, followed by one `name value` line per
+/// tuned parameter. The values aren't rounded, so that resuming doesn't lose precision.
+fn write_checkpoint(path: &Path, tuned: &[Tuned], state: &SpsaState) -> std::io::Result<()> {
+    let mut out = format!("# iteration {}\n", state.done);
+    for (t, value) in tuned.iter().zip(&state.theta) {
+        let _ = writeln!(out, "{} {value}", t.spec.name);
+    }
+
+    // Write to a temporary file first, so that an interrupted write can't corrupt the
+    // checkpoint.
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, out)?;
+    fs::rename(tmp, path)
+}
+
+fn read_checkpoint(path: &Path, tuned: &[Tuned], state: &mut SpsaState) -> Result<(), String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read checkpoint `{}`: {e}", path.display()))?;
+
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let invalid = || format!("Invalid checkpoint line `{line}`");
+
+        if let Some(comment) = line.strip_prefix('#') {
+            if let Some(done) = comment.trim().strip_prefix("iteration ") {
+                state.done = done.trim().parse().map_err(|_| invalid())?;
+            }
+            continue;
+        }
+
+        let (name, value) = line.split_once(' ').ok_or_else(invalid)?;
+        let value: f64 = value.trim().parse().map_err(|_| invalid())?;
+        // Parameters that aren't tuned this time keep their defaults.
+        if let Some(i) = tuned.iter().position(|t| t.spec.name == name) {
+            state.theta[i] = value;
+        }
+    }
+
+    Ok(())
+}
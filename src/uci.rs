@@ -1,33 +1,46 @@
 use std::{
     num::ParseIntError,
+    path::PathBuf,
     str::{FromStr, ParseBoolError},
 };
 
 use icarus_board::{board::Board, r#move::Move};
 
-use crate::bench::DEFAULT_BENCH_DEPTH;
+use crate::bench::{BenchConfig, DEFAULT_NNUE_BENCH_DEPTH};
 
 #[derive(Debug)]
 pub enum UciCommand {
     Uci,
     NewGame,
     IsReady,
+    Debug(bool),
     SetOption {
         name: String,
         value: String,
     },
     Position {
         board: Box<Board>,
-        moves: Vec<Move>,
+        /// The moves aren't parsed here, so that the engine can skip the moves it has already
+        /// played on its current position.
+        moves: Vec<String>,
         enable_960: bool,
     },
     Go(Vec<SearchLimit>),
     Eval,
     Display,
-    Bench {
-        depth: u8,
-        threads: u16,
-        hash: u32,
+    Bench(BenchConfig),
+    NnueBench(u8),
+    NetInfo {
+        net: Option<PathBuf>,
+        positions: Option<PathBuf>,
+    },
+    NetTransform {
+        net: Option<PathBuf>,
+        positions: Option<PathBuf>,
+        out: PathBuf,
+        prune: bool,
+        permute: bool,
+        compress: bool,
     },
     Perft {
         depth: u8,
@@ -46,6 +59,7 @@ pub enum UciCommand {
     Stop,
     Quit,
     Wait,
+    Stats,
     #[cfg(feature = "tune")]
     Params,
 }
@@ -76,6 +90,10 @@ pub enum UciParseError {
     MissingOptionValueToken,
     #[error("Missing option value on `setoption` command")]
     MissingOptionValue,
+    #[error("Missing `on` or `off` on `debug` command")]
+    MissingDebugValue,
+    #[error("Invalid `debug` value `{0}`, expected `on` or `off`")]
+    InvalidDebugValue(String),
     #[error("Missing `fen` or `startpos` on `position` command")]
     MissingPositionType,
     #[error("Missing a Scharnagl number")]
@@ -100,6 +118,14 @@ pub enum UciParseError {
     MissingRandomMovesToken,
     #[error("Missing `random_moves` value in `genfens` command")]
     MissingRandomMovesValue,
+    #[error("Unknown bench option `{0}`")]
+    UnknownBenchOption(String),
+    #[error("Missing value for bench option `{0}`")]
+    MissingBenchValue(String),
+    #[error("Unknown netinfo/nettransform option `{0}`")]
+    UnknownNetInfoOption(String),
+    #[error("Missing value for netinfo/nettransform option `{0}`")]
+    MissingNetInfoValue(String),
     #[error("Invalid FEN `{0}`")]
     InvalidFen(String),
     #[error("Missing `moves` token on `position` command")]
@@ -133,6 +159,12 @@ impl UciCommand {
             "stop" => Ok(Stop),
             "quit" | "q" => Ok(Quit),
             "wait" => Ok(Wait),
+            "stats" => Ok(Stats),
+            "debug" => match reader.next().ok_or(MissingDebugValue)? {
+                "on" => Ok(Debug(true)),
+                "off" => Ok(Debug(false)),
+                value => Err(InvalidDebugValue(value.into())),
+            },
             #[cfg(feature = "tune")]
             "params" => Ok(Params),
             "setoption" => {
@@ -140,20 +172,37 @@ impl UciCommand {
                     return Err(MissingOptionNameToken);
                 }
 
-                let name = reader.next().ok_or(MissingOptionName)?;
-                let Some(value_token) = reader.next() else {
+                // Option names may consist of multiple words, e.g. `Skill Level`.
+                let mut name = String::new();
+                let mut has_value = false;
+                for token in reader.by_ref() {
+                    if token == "value" {
+                        has_value = true;
+                        break;
+                    }
+                    if !name.is_empty() {
+                        name.push(' ');
+                    }
+                    name.push_str(token);
+                }
+                if name.is_empty() {
+                    return Err(MissingOptionName);
+                }
+                // Without a `value` token, only a single word name is accepted, since e.g.
+                // `setoption name Hash 16` is more likely a typo than a multi-word option.
+                if !has_value {
+                    if name.contains(' ') {
+                        return Err(MissingOptionValueToken);
+                    }
                     return Ok(SetOption {
-                        name: name.into(),
+                        name,
                         value: "<empty>".into(),
                     });
-                };
-                if value_token != "value" {
-                    return Err(MissingOptionValueToken);
                 }
 
                 let value = reader.next().ok_or(MissingOptionValue)?;
                 Ok(SetOption {
-                    name: name.into(),
+                    name,
                     value: value.into(),
                 })
             }
@@ -166,15 +215,94 @@ impl UciCommand {
                     value: value.into(),
                 })
             }
-            // bench <depth> <threads> <hash>
+            // bench [<depth> [<threads> [<hash>]]] [depth <d> | nodes <n> | movetime <ms>]
+            //       [threads <n>] [hash <mb>] [file <path>] [verbose] [json]
             "bench" => {
-                let depth = reader.next().map_or(Ok(DEFAULT_BENCH_DEPTH), str::parse)?;
-                let threads = reader.next().unwrap_or("1").parse()?;
-                let hash = reader.next().unwrap_or("16").parse()?;
-                Ok(Bench {
-                    depth,
-                    threads,
-                    hash,
+                let mut config = BenchConfig::default();
+                let mut reader = reader.peekable();
+
+                // The positional form is what OpenBench uses.
+                if let Some(depth) = reader.next_if(|s| s.parse::<u16>().is_ok()) {
+                    config.limit = SearchLimit::Depth(depth.parse()?);
+                }
+                if let Some(threads) = reader.next_if(|s| s.parse::<u32>().is_ok()) {
+                    config.threads = threads.parse()?;
+                }
+                if let Some(hash) = reader.next_if(|s| s.parse::<u64>().is_ok()) {
+                    config.hash = hash.parse()?;
+                }
+
+                while let Some(option) = reader.next() {
+                    let mut value = || {
+                        reader
+                            .next()
+                            .ok_or_else(|| MissingBenchValue(option.into()))
+                    };
+                    match option {
+                        "depth" => config.limit = SearchLimit::Depth(value()?.parse()?),
+                        "nodes" => config.limit = SearchLimit::Nodes(value()?.parse()?),
+                        "movetime" => config.limit = SearchLimit::MoveTime(value()?.parse()?),
+                        "threads" => config.threads = value()?.parse()?,
+                        "hash" => config.hash = value()?.parse()?,
+                        "file" => config.file = Some(value()?.into()),
+                        "verbose" => config.verbose = true,
+                        "json" => config.json = true,
+                        _ => return Err(UnknownBenchOption(option.into())),
+                    }
+                }
+
+                Ok(Bench(config))
+            }
+            // nnuebench [depth]
+            "nnuebench" => Ok(NnueBench(
+                reader
+                    .next()
+                    .map(str::parse)
+                    .transpose()?
+                    .unwrap_or(DEFAULT_NNUE_BENCH_DEPTH),
+            )),
+            // netinfo [net <path>] [positions <path>]
+            "netinfo" => {
+                let (mut net, mut positions) = (None, None);
+                while let Some(option) = reader.next() {
+                    let value = reader
+                        .next()
+                        .ok_or_else(|| MissingNetInfoValue(option.into()))?;
+                    match option {
+                        "net" => net = Some(value.into()),
+                        "positions" => positions = Some(value.into()),
+                        _ => return Err(UnknownNetInfoOption(option.into())),
+                    }
+                }
+                Ok(NetInfo { net, positions })
+            }
+            // nettransform out <path> [net <path>] [positions <path>] [prune] [permute] [compress]
+            "nettransform" => {
+                let (mut net, mut positions, mut out) = (None, None, None);
+                let (mut prune, mut permute, mut compress) = (false, false, false);
+                while let Some(option) = reader.next() {
+                    let mut value = || {
+                        reader
+                            .next()
+                            .ok_or_else(|| MissingNetInfoValue(option.into()))
+                    };
+                    match option {
+                        "net" => net = Some(value()?.into()),
+                        "positions" => positions = Some(value()?.into()),
+                        "out" => out = Some(value()?.into()),
+                        "prune" => prune = true,
+                        "permute" => permute = true,
+                        "compress" => compress = true,
+                        _ => return Err(UnknownNetInfoOption(option.into())),
+                    }
+                }
+                Ok(NetTransform {
+                    net,
+                    positions,
+                    out: out.ok_or_else(|| MissingNetInfoValue("out".into()))?,
+                    prune,
+                    permute,
+                    compress,
                 })
             }
             "perft" => Ok(Perft {
@@ -232,23 +360,9 @@ impl UciCommand {
                     return Err(MissingPositionMovesToken);
                 }
 
-                let mut current = startpos;
-                let mut moves = vec![];
-                for part in reader {
-                    let mv = current
-                        .parse_move(part, chess960 || enable_960)
-                        .ok_or_else(|| InvalidMove(part.to_string()))?;
-
-                    if !current.is_legal_thorough(mv) {
-                        return Err(InvalidMove(part.to_string()));
-                    }
-                    moves.push(mv);
-                    current.make_move(mv);
-                }
-
                 Ok(Position {
                     board: Box::new(startpos),
-                    moves,
+                    moves: reader.map(String::from).collect(),
                     enable_960,
                 })
             }
@@ -3,14 +3,20 @@ use std::sync::{
     atomic::{AtomicU64, Ordering},
 };
 
+use crate::util::turnstile::Turnstile;
+
 pub const FLUSH_THRESHOLD: u64 = 1024;
 
 /// A local counter, doing buffered writes to a backing atomic counter. To prevent excessive writes to the
 /// backing atomic, we only write to it every `FLUSH_THRESHOLD` increments, or when `flush()` is called.
+///
+/// If a `Turnstile` is attached, the counter additionally hands over the turn to the next thread every
+/// `FLUSH_THRESHOLD` increments, so that each thread runs for a fixed quota of increments at a time.
 pub struct BufferedCounter {
     global: Arc<AtomicU64>,
     local: u64,
     buffer: u64,
+    turnstile: Option<(Arc<Turnstile>, usize)>,
 }
 
 impl BufferedCounter {
@@ -19,6 +25,20 @@ impl BufferedCounter {
             global: count,
             local: 0,
             buffer: 0,
+            turnstile: None,
+        }
+    }
+
+    /// Attaches a turnstile, on which this counter takes turns as thread `id`. Passing `None` detaches it.
+    pub fn set_turnstile(&mut self, turnstile: Option<(Arc<Turnstile>, usize)>) {
+        self.turnstile = turnstile;
+    }
+
+    /// Flushes the counter, and if a turnstile is attached, lets the other threads take their turn.
+    pub fn sync(&mut self) {
+        self.flush();
+        if let Some((turnstile, id)) = &self.turnstile {
+            turnstile.pass_and_wait(*id);
         }
     }
 
@@ -31,7 +51,7 @@ impl BufferedCounter {
         self.local += 1;
         self.buffer += 1;
         if self.buffer >= FLUSH_THRESHOLD {
-            self.flush();
+            self.sync();
         }
     }
 
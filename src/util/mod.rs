@@ -1,6 +1,7 @@
 pub mod atomic_instant;
 pub mod buffered_counter;
 pub mod command_channel;
-mod tunable;
+pub mod tunable;
+pub mod turnstile;
 
 pub const MAX_PLY: u16 = 256;
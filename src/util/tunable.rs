@@ -1,15 +1,78 @@
+/// Name and range of a tunable parameter.
+#[derive(Clone, Copy, Debug)]
+pub struct ParamSpec {
+    pub name: &'static str,
+    pub min: f64,
+    pub max: f64,
+}
+
 #[macro_export]
 macro_rules! tunable_params {
 
     ($($name:ident : $ty:ty = $val:literal ($min:literal..=$max:literal);)*) => {
 
+        /// A full set of tunable parameters. Every search thread reads them from its own copy in
+        /// `ThreadCtx`, so threads of different engines (e.g. in SPSA self-play) can use
+        /// different sets.
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        pub struct Params {
+            $(pub $name: $ty,)*
+        }
+
+        impl Params {
+            pub const DEFAULT: Params = Params {
+                $($name: $val,)*
+            };
+
+            /// All parameters, in declaration order.
+            pub const SPECS: &[$crate::util::tunable::ParamSpec] = &[
+                $(
+                    $crate::util::tunable::ParamSpec {
+                        name: stringify!($name),
+                        min: $min as f64,
+                        max: $max as f64,
+                    },
+                )*
+            ];
+
+            pub fn get(&self, name: &str) -> Option<f64> {
+                match name {
+                    $(stringify!($name) => Some(self.$name as f64),)*
+                    _ => None,
+                }
+            }
+
+            /// Sets the parameter `name` to `val`, rounded to the nearest integer. Returns
+            /// `false` if there is no such parameter, or if `val` is out of its range.
+            pub fn set(&mut self, name: &str, val: f64) -> bool {
+                match name {
+                    $(
+                        stringify!($name) => {
+                            if !($min as f64..=$max as f64).contains(&val) {
+                                return false;
+                            }
+                            self.$name = val.round() as $ty;
+                            true
+                        }
+                    )*
+                    _ => false,
+                }
+            }
+        }
+
+        impl Default for Params {
+            fn default() -> Self {
+                Self::DEFAULT
+            }
+        }
+
         #[cfg(feature = "tune")]
-        pub fn list_params() {
+        pub fn list_params(params: &Params) {
             $(
                 println!(
                     "option name {} type spin default {} min {} max {}",
                     stringify!($name),
-                    $name(),
+                    params.$name,
                     $min,
                     $max,
                 );
@@ -17,64 +80,19 @@ macro_rules! tunable_params {
         }
 
         #[cfg(feature = "tune")]
-        pub fn valid_param_name(name: &str) -> bool {
-            match name {
-                $(stringify!($name) => true,)*
-                _ => false,
-            }
-        }
-
-        #[cfg(feature = "tune")]
-        pub fn set_param(name: &str, val: &str) {
-            match name {
-                $(
-                    stringify!($name) => unsafe { *vals::$name.0.get() = val.parse().expect("Invalid param!") },
-                )*
-                _ => println!("info error unknown option"),
-            }
-        }
-
-        #[cfg(feature = "tune")]
-        pub fn print_params_ob() {
+        pub fn print_params_ob(params: &Params) {
             $(
                 let step = ($max as f32 - $min as f32) / 20.0;
                 println!(
                     "{}, int, {:.1}, {:.1}, {:.1}, {}, 0.002",
                     stringify!($name),
-                    $name() as f32,
+                    params.$name as f32,
                     $min as f32,
                     $max as f32,
                     step,
                 );
             )*
         }
-
-        #[cfg(feature = "tune")]
-        mod vals {
-            #![allow(non_upper_case_globals)]
-            use std::cell::UnsafeCell;
-            pub struct SyncUnsafeCell<T>(pub UnsafeCell<T>);
-
-            unsafe impl<T: Sync> Sync for SyncUnsafeCell<T> {}
-
-            $(
-                pub static $name: SyncUnsafeCell<$ty> = SyncUnsafeCell(UnsafeCell::new($val));
-            )*
-        }
-
-        $(
-            #[cfg(feature = "tune")]
-            #[inline]
-            pub fn $name() -> $ty {
-                unsafe { *vals::$name.0.get() }
-            }
-
-            #[cfg(not(feature = "tune"))]
-            #[inline]
-            pub fn $name() -> $ty {
-                $val
-            }
-        )*
     };
 }
 
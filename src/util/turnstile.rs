@@ -0,0 +1,75 @@
+//! Lets a fixed set of threads take turns running in a fixed order. This is used to make
+//! multi-threaded searches deterministic: only one thread searches at any given time, and the
+//! points at which threads hand over their turn only depend on their own node counts.
+
+use std::sync::atomic::{
+    AtomicBool, AtomicU32,
+    Ordering::{Acquire, Relaxed, Release},
+};
+
+use crate::search::searcher::MAX_THREADS;
+
+pub struct Turnstile {
+    /// Id of the thread whose turn it currently is.
+    turn: AtomicU32,
+    num_threads: AtomicU32,
+    /// Threads that are done and must be skipped when handing over the turn.
+    done: Box<[AtomicBool]>,
+}
+
+impl Default for Turnstile {
+    fn default() -> Self {
+        Self {
+            turn: AtomicU32::new(0),
+            num_threads: AtomicU32::new(0),
+            done: (0..MAX_THREADS).map(|_| AtomicBool::new(false)).collect(),
+        }
+    }
+}
+
+impl Turnstile {
+    /// Resets the turnstile for `num_threads` threads, giving the first turn to thread 0.
+    /// Must not be called while any threads are using the turnstile.
+    pub fn reset(&self, num_threads: usize) {
+        self.done[..num_threads]
+            .iter()
+            .for_each(|d| d.store(false, Relaxed));
+        self.num_threads.store(num_threads as u32, Relaxed);
+        self.turn.store(0, Release);
+    }
+
+    /// Blocks until it's the turn of thread `id`.
+    pub fn wait(&self, id: usize) {
+        let mut turn = self.turn.load(Acquire);
+        while turn != id as u32 {
+            atomic_wait::wait(&self.turn, turn);
+            turn = self.turn.load(Acquire);
+        }
+    }
+
+    /// Hands the turn from thread `id` to the next thread that isn't done yet. If there
+    /// is no such thread, thread `id` keeps its turn. Must only be called during `id`'s turn.
+    fn pass(&self, id: usize) {
+        let n = self.num_threads.load(Relaxed) as usize;
+        let next = (1..n)
+            .map(|i| (id + i) % n)
+            .find(|&i| !self.done[i].load(Relaxed));
+
+        if let Some(next) = next {
+            self.turn.store(next as u32, Release);
+            atomic_wait::wake_all(&self.turn);
+        }
+    }
+
+    /// Hands over the turn and blocks until it's the turn of thread `id` again.
+    pub fn pass_and_wait(&self, id: usize) {
+        self.pass(id);
+        self.wait(id);
+    }
+
+    /// Marks thread `id` as done and hands over the turn for good.
+    pub fn finish(&self, id: usize) {
+        self.done[id].store(true, Relaxed);
+        self.pass(id);
+    }
+}
@@ -1,15 +1,104 @@
+use std::{fs, path::Path, sync::RwLock};
+
 use crate::score::Score;
 
-pub fn wdl_params(mat: i16) -> (f64, f64) {
-    let a = [-36.24535760, 49.95573192, -171.69014901, 476.10858668];
-    let b = [-82.69201609, 218.83093958, -140.40735385, 108.56006144];
+/// The coefficients of the cubic polynomials in the material count that give the parameters of
+/// the WDL model. `a` is the score at which a win is 50% likely, `b` the spread of the win
+/// probability around it. They depend on the net's eval scale, and can be refitted to datagen
+/// games with the `wdlfit` subcommand.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WdlModel {
+    pub a: [f64; 4],
+    pub b: [f64; 4],
+}
 
-    let m = mat.clamp(17, 78) as f64 / 58.0;
+static MODEL: RwLock<WdlModel> = RwLock::new(WdlModel::DEFAULT);
 
-    (
-        ((a[0] * m + a[1]) * m + a[2]) * m + a[3],
-        ((b[0] * m + b[1]) * m + b[2]) * m + b[3],
-    )
+impl WdlModel {
+    pub const DEFAULT: Self = Self {
+        a: [-36.24535760, 49.95573192, -171.69014901, 476.10858668],
+        b: [-82.69201609, 218.83093958, -140.40735385, 108.56006144],
+    };
+
+    /// The material count, as in `Board::classical_material`, mapped to the input of the
+    /// polynomials.
+    pub fn material_input(mat: i16) -> f64 {
+        mat.clamp(17, 78) as f64 / 58.0
+    }
+
+    pub fn params(&self, mat: i16) -> (f64, f64) {
+        self.params_at(Self::material_input(mat))
+    }
+
+    /// The parameters at the already mapped material input `m`.
+    pub fn params_at(&self, m: f64) -> (f64, f64) {
+        let (a, b) = (self.a, self.b);
+
+        (
+            ((a[0] * m + a[1]) * m + a[2]) * m + a[3],
+            ((b[0] * m + b[1]) * m + b[2]) * m + b[3],
+        )
+    }
+
+    /// Parses a model from an `a` and a `b` line, each with the four coefficients from the highest
+    /// power down. `#` starts a comment.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (mut a, mut b) = (None, None);
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid = |msg: &str| format!("Line {}: {msg} `{line}`", i + 1);
+            let mut tokens = line.split_whitespace();
+            let target = match tokens.next() {
+                Some("a") => &mut a,
+                Some("b") => &mut b,
+                _ => return Err(invalid("Expected `a` or `b`, got")),
+            };
+            let coeffs = tokens
+                .map(|t| t.parse::<f64>().ok().filter(|v| v.is_finite()))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| invalid("Invalid coefficient in"))?;
+            *target = Some(
+                coeffs
+                    .try_into()
+                    .map_err(|_| invalid("Expected four coefficients in"))?,
+            );
+        }
+
+        match (a, b) {
+            (Some(a), Some(b)) => Ok(Self { a, b }),
+            _ => Err("Expected both an `a` and a `b` line".to_string()),
+        }
+    }
+
+    /// Formats the model in the format accepted by `parse`.
+    pub fn to_text(&self) -> String {
+        let line = |name: &str, coeffs: &[f64; 4]| {
+            let coeffs: Vec<String> = coeffs.iter().map(|c| format!("{c:.8}")).collect();
+            format!("{name} {}\n", coeffs.join(" "))
+        };
+        line("a", &self.a) + &line("b", &self.b)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read `{}`: {e}", path.display()))?;
+        Self::parse(&text).map_err(|e| format!("`{}`: {e}", path.display()))
+    }
+}
+
+/// Makes `model` the one used by all functions in this module, e.g. after loading it through
+/// the `WdlModel` option.
+pub fn set_model(model: WdlModel) {
+    *MODEL.write().unwrap() = model;
+}
+
+pub fn wdl_params(mat: i16) -> (f64, f64) {
+    MODEL.read().unwrap().params(mat)
 }
 
 pub fn wdl_model(score: Score, mat: i16) -> (i16, i16) {
@@ -31,3 +120,32 @@ pub fn normalize(score: Score, mat: i16) -> Score {
     let normalized = (score.0 as f64) / a;
     Score::clamp_nomate(f64::round(normalized * 100.0) as i16)
 }
+
+/// Inverse of `normalize`, converting centipawns into an internal score.
+pub fn denormalize(score: Score, mat: i16) -> Score {
+    if score == Score::ZERO || score.is_mate() {
+        return score;
+    }
+
+    let a = wdl_params(mat).0;
+    Score::clamp_nomate(f64::round(score.0 as f64 * a / 100.0) as i16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WdlModel;
+
+    #[test]
+    fn parse_model() {
+        let model = WdlModel::DEFAULT;
+        assert_eq!(WdlModel::parse(&model.to_text()), Ok(model));
+
+        let parsed = WdlModel::parse("# fitted\nb 0 0 0 100\na 0 0 1 300 # comment\n").unwrap();
+        assert_eq!(parsed.params(58), (301.0, 100.0));
+
+        assert!(WdlModel::parse("a 1 2 3 4").is_err());
+        assert!(WdlModel::parse("a 1 2 3\nb 1 2 3 4").is_err());
+        assert!(WdlModel::parse("a 1 2 3 x\nb 1 2 3 4").is_err());
+        assert!(WdlModel::parse("c 1 2 3 4").is_err());
+    }
+}